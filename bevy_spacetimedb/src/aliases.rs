@@ -1,8 +1,8 @@
 use bevy::prelude::MessageReader;
 
 use crate::{
-    DeleteEvent, InsertEvent, InsertUpdateEvent, ReducerResultEvent, StdbConnectedEvent,
-    StdbConnectionErrorEvent, StdbDisconnectedEvent, UpdateEvent,
+    AnyReducerEvent, DeleteEvent, InsertEvent, InsertUpdateEvent, ReducerResultEvent,
+    StdbConnectedEvent, StdbConnectionErrorEvent, StdbDisconnectedEvent, UpdateEvent,
 };
 
 /// A type alias for a Bevy event reader for InsertEvent<T>.
@@ -20,6 +20,9 @@ pub type ReadInsertUpdateEvent<'w, 's, T> = MessageReader<'w, 's, InsertUpdateEv
 /// A type alias for a Bevy event reader for ReducerResultEvent<T>.
 pub type ReadReducerEvent<'w, 's, T> = MessageReader<'w, 's, ReducerResultEvent<T>>;
 
+/// A type alias for a Bevy event reader for AnyReducerEvent<R>, `R` being the module's `Reducer` enum.
+pub type ReadAnyReducerEvent<'w, 's, R> = MessageReader<'w, 's, AnyReducerEvent<R>>;
+
 /// A type alias for a Bevy event reader for StdbConnectedEvent.
 pub type ReadStdbConnectedEvent<'w, 's> = MessageReader<'w, 's, StdbConnectedEvent>;
 
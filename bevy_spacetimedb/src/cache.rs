@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use spacetimedb_sdk::Table;
+
+use crate::{DeleteEvent, InsertEvent, RowChangeEvent, UpdateEvent};
+
+/// Returns the handle of a registered table, as a Bevy resource read by [`StdbTable`].
+#[derive(Resource)]
+pub(crate) struct StdbTableAccess<TTable> {
+    handle: Arc<dyn Fn() -> TTable + Send + Sync>,
+}
+
+impl<TTable> StdbTableAccess<TTable> {
+    pub(crate) fn new(handle: impl Fn() -> TTable + Send + Sync + 'static) -> Self {
+        Self {
+            handle: Arc::new(handle),
+        }
+    }
+}
+
+/// The messages of type `T`, if they are registered.
+type TableMessages<'w, T> = Option<Res<'w, Messages<T>>>;
+
+/// Read-only access to a table of the client cache registered with [`crate::StdbPlugin::add_table`],
+/// e.g. `StdbTable<PlayersTableHandle<'static>>`, as a Bevy `SystemParam`.
+///
+/// It only reads resources, so the systems using it can run in parallel with each other.
+///
+/// ```ignore
+/// fn show_player(players: StdbTable<PlayersTableHandle<'static>>, stdb: SpacetimeDB) {
+///     if players.changed_this_frame()
+///         && let Some(player) = players.table().id().find(&stdb.identity())
+///     {
+///         info!("{player:?}");
+///     }
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct StdbTable<'w, TTable>
+where
+    TTable: Table<Row: Send + Sync + 'static> + 'static,
+{
+    access: Res<'w, StdbTableAccess<TTable>>,
+    inserted: TableMessages<'w, InsertEvent<<TTable as Table>::Row>>,
+    deleted: TableMessages<'w, DeleteEvent<<TTable as Table>::Row>>,
+    updated: TableMessages<'w, UpdateEvent<<TTable as Table>::Row>>,
+    changed: TableMessages<'w, RowChangeEvent<<TTable as Table>::Row>>,
+}
+
+impl<TTable> StdbTable<'_, TTable>
+where
+    TTable: Table<Row: Send + Sync + 'static> + 'static,
+{
+    /// The handle of the table, giving access to its rows and unique indexes,
+    /// e.g. `players.table().id().find(&id)`.
+    pub fn table(&self) -> TTable {
+        (self.access.handle)()
+    }
+
+    /// The number of rows in the table.
+    pub fn count(&self) -> u64 {
+        self.table().count()
+    }
+
+    /// Whether a row of the table was inserted, updated or deleted this frame,
+    /// according to the table events registered for it.
+    pub fn changed_this_frame(&self) -> bool {
+        fn written<T: Message>(messages: &TableMessages<T>) -> bool {
+            messages
+                .as_ref()
+                .is_some_and(|messages| messages.iter_current_update_messages().next().is_some())
+        }
+
+        written(&self.inserted)
+            || written(&self.deleted)
+            || written(&self.updated)
+            || written(&self.changed)
+    }
+}
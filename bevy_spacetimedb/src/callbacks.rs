@@ -0,0 +1,44 @@
+use bevy::{app::App, ecs::message::Message};
+use spacetimedb_sdk::__codegen as spacetime_codegen;
+
+use crate::{AddEventChannelAppExtensions, StdbPlugin, StdbSender};
+
+impl<
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext,
+    M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
+> StdbPlugin<C, M>
+{
+    /// Bridges any SpacetimeDB callback to the Bevy message `T`, for SDK features the plugin doesn't wrap.
+    ///
+    /// Once the connection is built, `register` is called with it and a [`StdbSender`] writing `T` messages,
+    /// which it moves into the callback it registers:
+    ///
+    /// ```ignore
+    /// #[derive(Message)]
+    /// struct PlayersLoaded;
+    ///
+    /// StdbPlugin::default()
+    ///     .add_callback_message::<PlayersLoaded, _>(|conn, sender| {
+    ///         conn.subscription_builder()
+    ///             .on_applied(move |_ctx| sender.send(PlayersLoaded))
+    ///             .subscribe("SELECT * FROM players");
+    ///     })
+    /// ```
+    ///
+    /// Several callbacks can write the same message type, they share its event channel.
+    pub fn add_callback_message<T, F>(mut self, register: F) -> Self
+    where
+        T: Message,
+        F: Fn(&'static C, StdbSender<T>) + Send + Sync + 'static,
+    {
+        self.channel_claims.shared::<T>();
+        let register_fn = move |app: &mut App, conn: &'static C| {
+            register(conn, app.stdb_event_sender::<T>());
+        };
+
+        // The register_fn will get called once the connection is built.
+        self.callback_registers.push(Box::new(register_fn));
+
+        self
+    }
+}
@@ -1,42 +1,971 @@
-// Source: https://github.com/bevyengine/bevy/issues/8983
-// This introduces event channels, on one side of which is mpsc::Sender<T>, and on another
-// side is bevy's EventReader<T>, and it automatically bridges between the two.
+// Originally based on: https://github.com/bevyengine/bevy/issues/8983
+// This introduces event channels, on one side of which is a crossbeam Sender<T>, and on another
+// side is bevy's MessageReader<T>, and it automatically bridges between the two.
+// All the channels of a schedule are drained by a single system, in registration order.
 
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
+use bevy::log::tracing;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::platform::time::Instant;
 use bevy::prelude::*;
-use std::sync::Mutex;
-use std::sync::mpsc::Receiver;
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use std::any::{Any, TypeId, type_name};
+use std::collections::VecDeque;
+use std::time::Duration;
 
-#[derive(Resource, Deref, DerefMut)]
-struct ChannelReceiver<T>(Mutex<Receiver<T>>);
+use crate::{StdbPluginError, runner::FrameTickSet};
 
-/// Allows to register an event channel backed by a `mpsc::Receiver<T>`.
+/// The system sets in which SpacetimeDB messages are handled, in each schedule draining event channels.
+///
+/// They run in the order `Drain`, `ApplyMirror`, `Dispatch`, order your systems against them,
+/// e.g. `.after(StdbSet::Dispatch)` to read the messages drained during the same frame.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StdbSet {
+    /// The event channels are drained, writing the received messages.
+    Drain,
+    /// The messages are applied to the world, e.g. spawning an entity per row.
+    ApplyMirror,
+    /// The messages are dispatched to the systems and observers reacting to them.
+    Dispatch,
+}
+
+/// What happens when a message is sent to an event channel that is full,
+/// see [`StdbChannelConfig::capacity`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Block the sender until the channel is drained, slowing down the connection.
+    ///
+    /// It can't be used with [`crate::StdbPlugin::with_frame_tick`], as the channels are drained on the same thread,
+    /// the plugin reports [`crate::StdbConfigError::BlockingFrameTick`] instead.
+    #[default]
+    Block,
+    /// Drop the message being sent.
+    DropNewest,
+    /// Drop the oldest message of the channel to make room for the one being sent.
+    DropOldest,
+}
+
+/// Configuration of the channels bridging SpacetimeDB callbacks to Bevy messages, as a Bevy resource.
+///
+/// Set it with [`crate::StdbPlugin::with_channel_capacity`], [`crate::StdbPlugin::with_backpressure`]
+/// and [`crate::StdbPlugin::with_drain_schedule`], it applies to the channels registered afterwards.
+#[derive(Resource, Debug, Clone)]
+pub struct StdbChannelConfig {
+    /// The maximum number of pending messages per channel, unbounded if `None`.
+    pub capacity: Option<usize>,
+    /// What happens when a message is sent to a full channel.
+    pub backpressure: BackpressurePolicy,
+    /// The schedule in which the channels are drained, `PreUpdate` by default.
+    pub schedule: InternedScheduleLabel,
+    /// The schedule in which the channel of a message type is drained, instead of [`Self::schedule`].
+    pub message_schedules: HashMap<TypeId, InternedScheduleLabel>,
+    /// Whether table events are also triggered for observers, see [`crate::StdbPlugin::with_triggered_events`].
+    pub trigger_events: bool,
+}
+
+impl Default for StdbChannelConfig {
+    fn default() -> Self {
+        Self {
+            capacity: None,
+            backpressure: BackpressurePolicy::default(),
+            schedule: PreUpdate.intern(),
+            message_schedules: HashMap::default(),
+            trigger_events: false,
+        }
+    }
+}
+
+impl StdbChannelConfig {
+    /// The schedule in which the channel of `T` is drained.
+    pub fn schedule_of<T: Message>(&self) -> InternedScheduleLabel {
+        self.message_schedules
+            .get(&TypeId::of::<T>())
+            .copied()
+            .unwrap_or(self.schedule)
+    }
+
+    /// Creates a channel following this configuration, to be registered with
+    /// [`AddEventChannelAppExtensions::try_add_event_channel`].
+    pub(crate) fn channel<T>(&self) -> (StdbSender<T>, Receiver<T>) {
+        let (sender, receiver) = match self.capacity {
+            Some(capacity) => crossbeam_channel::bounded(capacity),
+            None => crossbeam_channel::unbounded(),
+        };
+
+        let sender = StdbSender {
+            sender,
+            receiver: receiver.clone(),
+            backpressure: self.backpressure,
+        };
+        (sender, receiver)
+    }
+}
+
+/// The sending half of an event channel, messages sent through it are written as Bevy messages
+/// by the event channels drain system.
+pub struct StdbSender<T> {
+    sender: Sender<T>,
+    // Used to drop the oldest message with BackpressurePolicy::DropOldest
+    receiver: Receiver<T>,
+    backpressure: BackpressurePolicy,
+}
+
+impl<T> Clone for StdbSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            receiver: self.receiver.clone(),
+            backpressure: self.backpressure,
+        }
+    }
+}
+
+impl<T> StdbSender<T> {
+    /// Sends a message, following the [`BackpressurePolicy`] of the channel if it is full.
+    ///
+    /// Messages sent after the app is dropped are discarded.
+    pub fn send(&self, message: T) {
+        let mut message = message;
+        loop {
+            let result = match self.backpressure {
+                BackpressurePolicy::Block => self.sender.send(message).map_err(|err| err.into()),
+                _ => self.sender.try_send(message),
+            };
+
+            match result {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => return,
+                Err(TrySendError::Full(_))
+                    if self.backpressure == BackpressurePolicy::DropNewest =>
+                {
+                    return;
+                }
+                Err(TrySendError::Full(rejected)) => {
+                    let _ = self.receiver.try_recv();
+                    message = rejected;
+                }
+            }
+        }
+    }
+
+    /// The number of messages waiting to be drained.
+    pub fn len(&self) -> usize {
+        self.sender.len()
+    }
+
+    /// Returns `true` if no messages are waiting to be drained.
+    pub fn is_empty(&self) -> bool {
+        self.sender.is_empty()
+    }
+}
+
+/// Limits how many messages are drained from the event channels each frame,
+/// spreading large subscriptions over several frames instead of a single long one.
+///
+/// Messages that don't fit in the budget stay in their channel until the next frames,
+/// see [`StdbBacklog`] and [`stdb_backlog_empty`]. No limit is set by default.
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub struct StdbEventBudget {
+    /// The maximum number of messages drained per message type each frame.
+    pub per_type: Option<usize>,
+    /// The maximum number of messages drained across all message types each frame.
+    pub global: Option<usize>,
+    /// The time after which draining stops for the frame.
+    pub time: Option<Duration>,
+}
+
+impl StdbEventBudget {
+    /// Drain at most `count` messages per message type each frame.
+    pub fn per_type(count: usize) -> Self {
+        Self {
+            per_type: Some(count),
+            ..Default::default()
+        }
+    }
+
+    /// Drain at most `count` messages across all message types each frame.
+    pub fn global(count: usize) -> Self {
+        Self {
+            global: Some(count),
+            ..Default::default()
+        }
+    }
+
+    /// Stop draining messages once `time` is spent each frame.
+    pub fn time(time: Duration) -> Self {
+        Self {
+            time: Some(time),
+            ..Default::default()
+        }
+    }
+}
+
+/// The number of messages waiting in the event channels after they were drained this frame, as a Bevy resource.
+#[derive(Resource, Debug, Default)]
+pub struct StdbBacklog {
+    total: usize,
+    per_type: HashMap<&'static str, usize>,
+}
+
+impl StdbBacklog {
+    /// The number of messages waiting across all message types.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The number of messages of type `T` waiting.
+    pub fn of<T: Message>(&self) -> usize {
+        self.per_type.get(type_name::<T>()).copied().unwrap_or(0)
+    }
+
+    /// The number of messages waiting per message type name.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        self.per_type.iter().map(|(name, len)| (*name, *len))
+    }
+
+    /// Returns `true` if no messages are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+}
+
+/// The number of messages drained from the event channels since the app started, as a Bevy resource.
+#[derive(Resource, Debug, Default)]
+pub struct StdbMessageCounts {
+    total: usize,
+    per_type: HashMap<&'static str, usize>,
+}
+
+impl StdbMessageCounts {
+    /// The number of messages drained across all message types.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The number of messages of type `T` drained.
+    pub fn of<T: Message>(&self) -> usize {
+        self.per_type.get(type_name::<T>()).copied().unwrap_or(0)
+    }
+
+    /// The number of messages drained per message type name.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        self.per_type.iter().map(|(name, count)| (*name, *count))
+    }
+}
+
+/// A run condition which is `true` when all the messages received from SpacetimeDB were written,
+/// i.e. the [`StdbBacklog`] is empty.
+pub fn stdb_backlog_empty() -> impl FnMut(Option<Res<StdbBacklog>>) -> bool + Clone {
+    |backlog: Option<Res<StdbBacklog>>| backlog.is_none_or(|backlog| backlog.is_empty())
+}
+
+// Messages are drained in chunks so the time budget is checked regularly.
+const DRAIN_CHUNK: usize = 256;
+
+/// A channel of the bridge, with its message type erased.
+trait EventChannel: Send + Sync {
+    /// Writes up to `limit` pending messages into the world, returning how many were written.
+    fn drain(&self, world: &mut World, limit: usize) -> usize;
+
+    /// The number of pending messages.
+    fn len(&self) -> usize;
+
+    /// The name of the message type.
+    fn type_name(&self) -> &'static str;
+}
+
+impl<T: Message> EventChannel for Receiver<T> {
+    fn drain(&self, world: &mut World, limit: usize) -> usize {
+        let mut written = 0;
+        world.write_message_batch(self.try_iter().take(limit).inspect(|_| written += 1));
+        written
+    }
+
+    fn len(&self) -> usize {
+        Receiver::len(self)
+    }
+
+    fn type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+}
+
+struct RegisteredChannel {
+    channel: Box<dyn EventChannel>,
+    type_id: TypeId,
+    schedule: InternedScheduleLabel,
+}
+
+/// Writes a message taken from a barrier channel into the world.
+type WriteMessage = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+/// A channel whose messages are held until the messages sent before them to the other channels are drained,
+/// see [`barrier_sender`].
+trait BarrierChannel: Send + Sync {
+    /// Takes the pending messages.
+    fn receive(&self) -> Vec<WriteMessage>;
+}
+
+impl<T: Message> BarrierChannel for Receiver<T> {
+    fn receive(&self) -> Vec<WriteMessage> {
+        self.try_iter()
+            .map(|message| {
+                Box::new(move |world: &mut World| {
+                    world.write_message(message);
+                }) as WriteMessage
+            })
+            .collect()
+    }
+}
+
+struct RegisteredBarrier {
+    channel: Box<dyn BarrierChannel>,
+    schedule: InternedScheduleLabel,
+}
+
+/// A message taken from a barrier channel, waiting for the messages sent before it.
+struct HeldMessage {
+    write: WriteMessage,
+    /// The index of each channel which had pending messages, with the count of drained messages to reach.
+    required: Vec<(usize, usize)>,
+}
+
+/// All the registered event channels.
+#[derive(Resource, Default)]
+struct EventBridge {
+    channels: Vec<RegisteredChannel>,
+    // Senders created with `stdb_event_sender`, to be shared by later calls.
+    senders: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    // The schedules in which a drain system was added.
+    schedules: HashSet<InternedScheduleLabel>,
+    // The channel drained first per schedule, rotated every run so a global budget doesn't starve the last channels.
+    first: HashMap<InternedScheduleLabel, usize>,
+    // The message types also triggered as events for observers.
+    triggered: HashSet<TypeId>,
+    barriers: Vec<RegisteredBarrier>,
+    // The messages of the barrier channels per schedule, in the order they were received.
+    held: HashMap<InternedScheduleLabel, VecDeque<HeldMessage>>,
+}
+
+/// The message types whose event channels are registered once the connection is built,
+/// checked by [`crate::StdbPlugin::try_build`] before changing the app.
+#[derive(Default)]
+pub(crate) struct ChannelClaims {
+    // Registered with `try_add_event_sender`, which fails if the channel already exists.
+    exclusive: Vec<(TypeId, &'static str)>,
+    // Registered with `stdb_event_sender`, which shares the channel with the other callbacks.
+    shared: Vec<(TypeId, &'static str)>,
+}
+
+impl ChannelClaims {
+    /// Claims the event channel of `T`, which can only be registered once.
+    pub(crate) fn exclusive<T: Message>(&mut self) {
+        self.exclusive.push((TypeId::of::<T>(), type_name::<T>()));
+    }
+
+    /// Claims the event channel of `T`, which is shared by the callbacks writing `T`.
+    pub(crate) fn shared<T: Message>(&mut self) {
+        self.shared.push((TypeId::of::<T>(), type_name::<T>()));
+    }
+
+    /// Adds the channels claimed by `other`.
+    pub(crate) fn extend(&mut self, other: &ChannelClaims) {
+        self.exclusive.extend_from_slice(&other.exclusive);
+        self.shared.extend_from_slice(&other.shared);
+    }
+
+    /// Returns an error if one of the claimed channels would be registered twice,
+    /// or conflicts with a channel already registered in `app`.
+    pub(crate) fn check(&self, app: &App) -> Result<(), StdbPluginError> {
+        let bridge = app.world().get_resource::<EventBridge>();
+        let registered = |type_id: &TypeId| {
+            bridge.is_some_and(|bridge| {
+                bridge
+                    .channels
+                    .iter()
+                    .any(|registered| registered.type_id == *type_id)
+            })
+        };
+        let shared =
+            |type_id: &TypeId| bridge.is_some_and(|bridge| bridge.senders.contains_key(type_id));
+
+        let mut exclusive = HashSet::new();
+        for (type_id, type_name) in &self.exclusive {
+            if !exclusive.insert(*type_id) || registered(type_id) {
+                return Err(StdbPluginError::DuplicateEventChannel(type_name));
+            }
+        }
+        for (type_id, type_name) in &self.shared {
+            if exclusive.contains(type_id) || (registered(type_id) && !shared(type_id)) {
+                return Err(StdbPluginError::DuplicateEventChannel(type_name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Allows to register an event channel backed by a crossbeam `Receiver<T>`.
 /// This is useful in multithreaded applications where you want to send events from a different thread
 pub trait AddEventChannelAppExtensions {
-    /// Allows you to create bevy events using mpsc Sender
+    /// Allows you to create bevy events using a crossbeam Sender
+    ///
+    /// The channel is created with the re-exported [`crate::crossbeam_channel`], which replaces `std::sync::mpsc`:
+    ///
+    /// ```ignore
+    /// let (sender, receiver) = bevy_spacetimedb::crossbeam_channel::unbounded::<PlayersLoaded>();
+    /// app.add_event_channel(receiver);
+    /// ```
+    ///
+    /// Panics if a channel is already registered for `T`.
     fn add_event_channel<T: Message>(&mut self, receiver: Receiver<T>) -> &mut Self;
+
+    /// Same as [`Self::add_event_channel`], but returns an error if a channel is already registered for `T`.
+    fn try_add_event_channel<T: Message>(
+        &mut self,
+        receiver: Receiver<T>,
+    ) -> Result<&mut Self, StdbPluginError>;
+
+    /// Registers an event channel for `T` configured with the [`StdbChannelConfig`], returning its sending half.
+    ///
+    /// Returns an error if a channel is already registered for `T`.
+    fn try_add_event_sender<T: Message>(&mut self) -> Result<StdbSender<T>, StdbPluginError>;
+
+    /// Returns the sending half of the event channel for `T`, registering it if needed.
+    fn stdb_event_sender<T: Message>(&mut self) -> StdbSender<T>;
+
+    /// Bridges a SpacetimeDB callback to the message `T` once the connection is available,
+    /// `register` is given a [`StdbSender`] to move into the callback.
+    ///
+    /// ```ignore
+    /// let conn = app.world().resource::<StdbConnection<DbConnection>>().conn();
+    /// app.add_stdb_callback::<PlanetsLoaded>(|sender| {
+    ///     conn.subscription_builder()
+    ///         .on_applied(move |_ctx| sender.send(PlanetsLoaded))
+    ///         .subscribe("SELECT * FROM planets");
+    /// });
+    /// ```
+    fn add_stdb_callback<T: Message>(&mut self, register: impl FnOnce(StdbSender<T>)) -> &mut Self;
+
+    /// Drains the event channel of `T` in `schedule`, whether it is already registered or not.
+    fn set_event_channel_schedule<T: Message>(&mut self, schedule: impl ScheduleLabel)
+    -> &mut Self;
+
+    /// Also triggers the drained `T` messages as events, so observers can react to them.
+    ///
+    /// They are triggered in [`StdbSet::Dispatch`], the messages can still be read with a `MessageReader`.
+    fn trigger_stdb_message<T>(&mut self) -> &mut Self
+    where
+        T: Message + Event + Clone,
+        for<'a> T::Trigger<'a>: Default;
 }
 
 impl AddEventChannelAppExtensions for App {
     fn add_event_channel<T: Message>(&mut self, receiver: Receiver<T>) -> &mut Self {
-        assert!(
-            !self.world().contains_resource::<ChannelReceiver<T>>(),
-            "this SpacetimeDB event channel is already initialized",
-        );
+        match self.try_add_event_channel(receiver) {
+            Ok(app) => app,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    fn try_add_event_channel<T: Message>(
+        &mut self,
+        receiver: Receiver<T>,
+    ) -> Result<&mut Self, StdbPluginError> {
+        if !self.world().contains_resource::<EventBridge>() {
+            self.init_resource::<EventBridge>();
+            self.init_resource::<StdbEventBudget>();
+            self.init_resource::<StdbBacklog>();
+            self.init_resource::<StdbMessageCounts>();
+        }
 
+        let schedule = self
+            .world()
+            .get_resource::<StdbChannelConfig>()
+            .map_or_else(|| PreUpdate.intern(), |config| config.schedule_of::<T>());
+        let mut bridge = self.world_mut().resource_mut::<EventBridge>();
+        if bridge
+            .channels
+            .iter()
+            .any(|registered| registered.type_id == TypeId::of::<T>())
+        {
+            return Err(StdbPluginError::DuplicateEventChannel(type_name::<T>()));
+        }
+        bridge.channels.push(RegisteredChannel {
+            channel: Box::new(receiver),
+            type_id: TypeId::of::<T>(),
+            schedule,
+        });
+
+        add_drain_system(self, schedule);
         self.add_message::<T>();
-        self.add_systems(PreUpdate, channel_to_event::<T>);
-        self.insert_resource(ChannelReceiver(Mutex::new(receiver)));
+        Ok(self)
+    }
+
+    fn try_add_event_sender<T: Message>(&mut self) -> Result<StdbSender<T>, StdbPluginError> {
+        let config = self
+            .world()
+            .get_resource::<StdbChannelConfig>()
+            .cloned()
+            .unwrap_or_default();
+        let (sender, receiver) = config.channel::<T>();
+        self.try_add_event_channel(receiver)?;
+        Ok(sender)
+    }
+
+    fn stdb_event_sender<T: Message>(&mut self) -> StdbSender<T> {
+        let existing = self
+            .world()
+            .get_resource::<EventBridge>()
+            .and_then(|bridge| {
+                bridge
+                    .senders
+                    .get(&TypeId::of::<T>())
+                    .and_then(|sender| sender.downcast_ref::<StdbSender<T>>())
+                    .cloned()
+            });
+        if let Some(sender) = existing {
+            return sender;
+        }
+
+        let sender = match self.try_add_event_sender::<T>() {
+            Ok(sender) => sender,
+            Err(err) => panic!("{err}"),
+        };
+        self.world_mut()
+            .resource_mut::<EventBridge>()
+            .senders
+            .insert(TypeId::of::<T>(), Box::new(sender.clone()));
+        sender
+    }
+
+    fn add_stdb_callback<T: Message>(&mut self, register: impl FnOnce(StdbSender<T>)) -> &mut Self {
+        register(self.stdb_event_sender::<T>());
+        self
+    }
+
+    fn set_event_channel_schedule<T: Message>(
+        &mut self,
+        schedule: impl ScheduleLabel,
+    ) -> &mut Self {
+        let schedule = schedule.intern();
+        self.world_mut()
+            .get_resource_or_init::<StdbChannelConfig>()
+            .message_schedules
+            .insert(TypeId::of::<T>(), schedule);
+
+        let Some(mut bridge) = self.world_mut().get_resource_mut::<EventBridge>() else {
+            return self;
+        };
+        let Some(registered) = bridge
+            .channels
+            .iter_mut()
+            .find(|registered| registered.type_id == TypeId::of::<T>())
+        else {
+            return self;
+        };
+        registered.schedule = schedule;
+
+        add_drain_system(self, schedule);
         self
     }
+
+    fn trigger_stdb_message<T>(&mut self) -> &mut Self
+    where
+        T: Message + Event + Clone,
+        for<'a> T::Trigger<'a>: Default,
+    {
+        if !self
+            .world_mut()
+            .get_resource_or_init::<EventBridge>()
+            .triggered
+            .insert(TypeId::of::<T>())
+        {
+            return self;
+        }
+
+        let schedule = self
+            .world()
+            .get_resource::<StdbChannelConfig>()
+            .map_or_else(|| PreUpdate.intern(), |config| config.schedule_of::<T>());
+        add_drain_system(self, schedule);
+        self.add_message::<T>()
+            .add_systems(schedule, trigger_messages::<T>.in_set(StdbSet::Dispatch))
+    }
+}
+
+/// Triggers the `T` messages written this frame for the observers.
+fn trigger_messages<T>(mut messages: MessageReader<T>, mut commands: Commands)
+where
+    T: Message + Event + Clone,
+    for<'a> T::Trigger<'a>: Default,
+{
+    for message in messages.read() {
+        commands.trigger(message.clone());
+    }
+}
+
+/// Adds the system draining the channels of `schedule`, if it wasn't added yet.
+pub(crate) fn add_drain_system(app: &mut App, schedule: InternedScheduleLabel) {
+    if !app
+        .world_mut()
+        .get_resource_or_init::<EventBridge>()
+        .schedules
+        .insert(schedule)
+    {
+        return;
+    }
+
+    app.configure_sets(
+        schedule,
+        (StdbSet::Drain, StdbSet::ApplyMirror, StdbSet::Dispatch).chain(),
+    )
+    .configure_sets(schedule, FrameTickSet.before(StdbSet::Drain))
+    .add_systems(
+        schedule,
+        (move |world: &mut World| drain_event_channels(world, schedule)).in_set(StdbSet::Drain),
+    );
+}
+
+/// Registers a barrier channel for `T`, whose messages are only written once all the messages sent
+/// to the other channels before them were drained, and returns its sending half.
+pub(crate) fn barrier_sender<T: Message>(app: &mut App) -> StdbSender<T> {
+    let schedule = app
+        .world()
+        .get_resource::<StdbChannelConfig>()
+        .map_or_else(|| PreUpdate.intern(), |config| config.schedule_of::<T>());
+    // Unbounded, so the connection never blocks on it
+    let (sender, receiver) = crossbeam_channel::unbounded();
+
+    app.world_mut()
+        .get_resource_or_init::<EventBridge>()
+        .barriers
+        .push(RegisteredBarrier {
+            channel: Box::new(receiver.clone()),
+            schedule,
+        });
+    add_drain_system(app, schedule);
+    app.add_message::<T>();
+
+    StdbSender {
+        sender,
+        receiver,
+        backpressure: BackpressurePolicy::Block,
+    }
 }
 
-fn channel_to_event<T: 'static + Send + Sync + Message>(
-    receiver: Res<ChannelReceiver<T>>,
-    mut writer: MessageWriter<T>,
+/// Holds the messages received by the barrier channels of `schedule`,
+/// and writes the held ones whose preceding messages were all drained.
+fn release_barrier_messages(
+    world: &mut World,
+    bridge: &mut EventBridge,
+    schedule: InternedScheduleLabel,
 ) {
-    // this should be the only system working with the receiver,
-    // thus we always expect to get this lock
-    let events = receiver.lock().expect("unable to acquire mutex lock");
+    let counts = world.resource::<StdbMessageCounts>();
+    for barrier in bridge
+        .barriers
+        .iter()
+        .filter(|barrier| barrier.schedule == schedule)
+    {
+        let messages = barrier.channel.receive();
+        if messages.is_empty() {
+            continue;
+        }
+        // Taken before looking at the other channels, so their pending messages include the ones sent before
+        let required: Vec<(usize, usize)> = bridge
+            .channels
+            .iter()
+            .enumerate()
+            .filter(|(_, registered)| registered.channel.len() > 0)
+            .map(|(index, registered)| {
+                let type_name = registered.channel.type_name();
+                let drained = counts.per_type.get(type_name).copied().unwrap_or(0);
+                (index, drained + registered.channel.len())
+            })
+            .collect();
+        let held = bridge.held.entry(schedule).or_default();
+        held.extend(messages.into_iter().map(|write| HeldMessage {
+            write,
+            required: required.clone(),
+        }));
+    }
+
+    let Some(held) = bridge.held.get_mut(&schedule) else {
+        return;
+    };
+    let channels = &bridge.channels;
+    let is_drained = |message: &HeldMessage| {
+        message.required.iter().all(|(index, required)| {
+            let channel = &channels[*index].channel;
+            // Also released if some of the messages were dropped by the backpressure policy
+            channel.len() == 0
+                || counts
+                    .per_type
+                    .get(channel.type_name())
+                    .is_some_and(|drained| drained >= required)
+        })
+    };
+    // Released in the order they were received
+    let released = held
+        .iter()
+        .take_while(|message| is_drained(message))
+        .count();
+    let released: Vec<HeldMessage> = held.drain(..released).collect();
+    for message in released {
+        (message.write)(world);
+    }
+}
+
+fn drain_event_channels(world: &mut World, schedule: InternedScheduleLabel) {
+    let _span = info_span!("stdb_drain", schedule = ?schedule).entered();
+    let budget = *world.resource::<StdbEventBudget>();
+    let start = Instant::now();
+    let time_left = || budget.time.is_none_or(|time| start.elapsed() < time);
+
+    world.resource_scope(|world, mut bridge: Mut<EventBridge>| {
+        let channels: Vec<&RegisteredChannel> = bridge
+            .channels
+            .iter()
+            .filter(|registered| registered.schedule == schedule)
+            .collect();
+        if channels.is_empty() {
+            release_barrier_messages(world, &mut bridge, schedule);
+            return;
+        }
+
+        let first = bridge.first.get(&schedule).copied().unwrap_or(0);
+        let mut global_left = budget.global.unwrap_or(usize::MAX);
+        for i in 0..channels.len() {
+            let channel = &channels[(first + i) % channels.len()].channel;
+            let mut type_left = budget.per_type.unwrap_or(usize::MAX);
+
+            let span = info_span!(
+                "stdb_drain_channel",
+                message = %ShortName(channel.type_name()),
+                rows = tracing::field::Empty
+            )
+            .entered();
+            let mut drained = 0;
+            while type_left > 0 && global_left > 0 && time_left() {
+                let written = channel.drain(world, type_left.min(global_left).min(DRAIN_CHUNK));
+                type_left -= written;
+                global_left -= written;
+                drained += written;
+                if written < DRAIN_CHUNK {
+                    break;
+                }
+            }
+
+            span.record("rows", drained);
+
+            let mut counts = world.resource_mut::<StdbMessageCounts>();
+            *counts.per_type.entry(channel.type_name()).or_default() += drained;
+            counts.total += drained;
+        }
+
+        let mut backlog = world.resource_mut::<StdbBacklog>();
+        for registered in channels.iter() {
+            let len = registered.channel.len();
+            backlog.per_type.insert(registered.channel.type_name(), len);
+        }
+        backlog.total = backlog.per_type.values().sum();
 
-    writer.write_batch(events.try_iter());
+        let next = (first + 1) % channels.len();
+        bridge.first.insert(schedule, next);
+
+        release_barrier_messages(world, &mut bridge, schedule);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::message::Messages;
+
+    use super::*;
+
+    #[derive(Message)]
+    struct Ping;
+
+    #[derive(Message)]
+    struct Pong;
+
+    #[derive(Message, Debug, PartialEq)]
+    struct Number(u32);
+
+    #[derive(Message, Debug, PartialEq)]
+    struct Letter(char);
+
+    #[derive(Message)]
+    struct Barrier;
+
+    fn app_with(config: StdbChannelConfig, budget: StdbEventBudget) -> App {
+        let mut app = App::new();
+        app.insert_resource(config).insert_resource(budget);
+        app
+    }
+
+    fn bounded(capacity: usize, backpressure: BackpressurePolicy) -> StdbChannelConfig {
+        StdbChannelConfig {
+            capacity: Some(capacity),
+            backpressure,
+            ..Default::default()
+        }
+    }
+
+    fn drain(app: &mut App) {
+        drain_event_channels(app.world_mut(), PreUpdate.intern());
+    }
+
+    /// Takes the messages of type `T` written since the last call.
+    fn take<T: Message>(app: &mut App) -> Vec<T> {
+        app.world_mut()
+            .resource_mut::<Messages<T>>()
+            .drain()
+            .collect()
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_first_messages() {
+        let mut app = app_with(bounded(2, BackpressurePolicy::DropNewest), default());
+        let sender = app.try_add_event_sender::<Number>().unwrap();
+        for n in 1..=4 {
+            sender.send(Number(n));
+        }
+        assert_eq!(sender.len(), 2);
+
+        drain(&mut app);
+        assert_eq!(take::<Number>(&mut app), [Number(1), Number(2)]);
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_last_messages() {
+        let mut app = app_with(bounded(2, BackpressurePolicy::DropOldest), default());
+        let sender = app.try_add_event_sender::<Number>().unwrap();
+        for n in 1..=4 {
+            sender.send(Number(n));
+        }
+        assert_eq!(sender.len(), 2);
+
+        drain(&mut app);
+        assert_eq!(take::<Number>(&mut app), [Number(3), Number(4)]);
+    }
+
+    #[test]
+    fn per_type_budget_spreads_messages_over_frames() {
+        let mut app = app_with(default(), StdbEventBudget::per_type(2));
+        let numbers = app.try_add_event_sender::<Number>().unwrap();
+        let letters = app.try_add_event_sender::<Letter>().unwrap();
+        for n in 1..=5 {
+            numbers.send(Number(n));
+        }
+        letters.send(Letter('a'));
+
+        drain(&mut app);
+        assert_eq!(take::<Number>(&mut app), [Number(1), Number(2)]);
+        assert_eq!(take::<Letter>(&mut app), [Letter('a')]);
+        let backlog = app.world().resource::<StdbBacklog>();
+        assert_eq!((backlog.of::<Number>(), backlog.total()), (3, 3));
+
+        drain(&mut app);
+        drain(&mut app);
+        assert_eq!(take::<Number>(&mut app), [Number(3), Number(4), Number(5)]);
+        assert!(app.world().resource::<StdbBacklog>().is_empty());
+        assert_eq!(app.world().resource::<StdbMessageCounts>().total(), 6);
+    }
+
+    #[test]
+    fn global_budget_is_shared_by_the_channels() {
+        let mut app = app_with(default(), StdbEventBudget::global(3));
+        let numbers = app.try_add_event_sender::<Number>().unwrap();
+        let letters = app.try_add_event_sender::<Letter>().unwrap();
+        for n in 1..=2 {
+            numbers.send(Number(n));
+        }
+        for c in ['a', 'b', 'c'] {
+            letters.send(Letter(c));
+        }
+
+        drain(&mut app);
+        assert_eq!(take::<Number>(&mut app), [Number(1), Number(2)]);
+        assert_eq!(take::<Letter>(&mut app), [Letter('a')]);
+        assert_eq!(app.world().resource::<StdbBacklog>().total(), 2);
+    }
+
+    #[test]
+    fn first_channel_is_rotated_so_none_is_starved() {
+        let mut app = app_with(default(), StdbEventBudget::global(1));
+        let numbers = app.try_add_event_sender::<Number>().unwrap();
+        let letters = app.try_add_event_sender::<Letter>().unwrap();
+        for n in 1..=2 {
+            numbers.send(Number(n));
+        }
+        for c in ['a', 'b'] {
+            letters.send(Letter(c));
+        }
+
+        drain(&mut app);
+        assert_eq!(take::<Number>(&mut app), [Number(1)]);
+        assert_eq!(take::<Letter>(&mut app), []);
+
+        drain(&mut app);
+        assert_eq!(take::<Number>(&mut app), []);
+        assert_eq!(take::<Letter>(&mut app), [Letter('a')]);
+
+        drain(&mut app);
+        assert_eq!(take::<Number>(&mut app), [Number(2)]);
+    }
+
+    #[test]
+    fn barrier_messages_wait_for_the_messages_sent_before_them() {
+        let mut app = app_with(default(), StdbEventBudget::per_type(1));
+        let numbers = app.try_add_event_sender::<Number>().unwrap();
+        let barrier = barrier_sender::<Barrier>(&mut app);
+        numbers.send(Number(1));
+        numbers.send(Number(2));
+        barrier.send(Barrier);
+
+        drain(&mut app);
+        assert_eq!(take::<Barrier>(&mut app).len(), 0);
+
+        drain(&mut app);
+        assert_eq!(take::<Number>(&mut app), [Number(1), Number(2)]);
+        assert_eq!(take::<Barrier>(&mut app).len(), 1);
+    }
+
+    #[test]
+    fn claims_reject_channels_registered_twice() {
+        let app = App::new();
+        let mut claims = ChannelClaims::default();
+        claims.exclusive::<Ping>();
+        claims.shared::<Pong>();
+        claims.shared::<Pong>();
+        assert!(claims.check(&app).is_ok());
+
+        claims.exclusive::<Ping>();
+        assert!(matches!(
+            claims.check(&app),
+            Err(StdbPluginError::DuplicateEventChannel(name)) if name == type_name::<Ping>()
+        ));
+
+        let mut claims = ChannelClaims::default();
+        claims.exclusive::<Pong>();
+        claims.shared::<Pong>();
+        assert!(claims.check(&app).is_err());
+    }
+
+    #[test]
+    fn claims_reject_channels_already_registered() {
+        let mut app = App::new();
+        app.try_add_event_sender::<Ping>().unwrap();
+        app.stdb_event_sender::<Pong>();
+
+        let mut claims = ChannelClaims::default();
+        claims.shared::<Pong>();
+        assert!(claims.check(&app).is_ok());
+
+        claims.exclusive::<Pong>();
+        assert!(claims.check(&app).is_err());
+
+        let mut claims = ChannelClaims::default();
+        claims.shared::<Ping>();
+        assert!(claims.check(&app).is_err());
+    }
 }
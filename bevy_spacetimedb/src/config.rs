@@ -0,0 +1,553 @@
+use std::{env, fmt, io, path::PathBuf};
+
+use spacetimedb_sdk::Compression;
+
+/// Connection settings of the [`crate::StdbPlugin`], which can be loaded at runtime
+/// from environment variables or a configuration file, and applied with [`crate::StdbPlugin::with_config`].
+///
+/// Only the settings which are set override the ones of the plugin.
+///
+/// A configuration file looks like this in TOML (requires the `toml` feature):
+///
+/// ```toml
+/// uri = "https://maincloud.spacetimedb.com"
+/// module_name = "my_game"
+/// compression = "gzip"
+/// light_mode = true
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(any(feature = "ron", feature = "toml"), derive(serde::Deserialize))]
+#[cfg_attr(
+    any(feature = "ron", feature = "toml"),
+    serde(default, deny_unknown_fields)
+)]
+pub struct StdbConfig {
+    /// The URI of the SpacetimeDB host, see [`crate::StdbPlugin::with_uri`].
+    pub uri: Option<String>,
+    /// The name or identity of the remote module, see [`crate::StdbPlugin::with_module_name`].
+    pub module_name: Option<String>,
+    /// The token to authenticate with, see [`crate::StdbPlugin::with_token`].
+    pub token: Option<String>,
+    /// The compression to use, one of `none`, `gzip` or `brotli`, see [`crate::StdbPlugin::with_compression`].
+    #[cfg_attr(
+        any(feature = "ron", feature = "toml"),
+        serde(deserialize_with = "deserialize_compression")
+    )]
+    pub compression: Option<Compression>,
+    /// Whether the light mode is used, see [`crate::StdbPlugin::with_light_mode`].
+    pub light_mode: Option<bool>,
+}
+
+impl StdbConfig {
+    /// The environment variables read by [`Self::from_env`].
+    pub const ENV_VARS: [&str; 5] = [
+        "STDB_URI",
+        "STDB_MODULE",
+        "STDB_TOKEN",
+        "STDB_COMPRESSION",
+        "STDB_LIGHT_MODE",
+    ];
+
+    /// Loads the configuration from the `STDB_URI`, `STDB_MODULE`, `STDB_TOKEN`,
+    /// `STDB_COMPRESSION` and `STDB_LIGHT_MODE` environment variables.
+    ///
+    /// Unset variables are left as `None`, invalid values are reported as errors.
+    pub fn from_env() -> Result<Self, Vec<StdbConfigError>> {
+        Self::from_vars(|key| env::var(key).ok())
+    }
+
+    /// Loads the configuration from the variables returned by `lookup`, see [`Self::from_env`].
+    fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<StdbConfigError>> {
+        let var = |key: &str| lookup(key).filter(|value| !value.is_empty());
+        let mut errors = Vec::new();
+
+        let compression = var("STDB_COMPRESSION").and_then(|value| {
+            parse_compression(&value)
+                .inspect_err(|err| errors.push(err.clone().with_key("STDB_COMPRESSION")))
+                .ok()
+        });
+        let light_mode = var("STDB_LIGHT_MODE").and_then(|value| {
+            parse_bool(&value)
+                .inspect_err(|err| errors.push(err.clone().with_key("STDB_LIGHT_MODE")))
+                .ok()
+        });
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Self {
+            uri: var("STDB_URI"),
+            module_name: var("STDB_MODULE"),
+            token: var("STDB_TOKEN"),
+            compression,
+            light_mode,
+        })
+    }
+
+    /// Loads the configuration from a RON (`.ron`) or TOML (`.toml`) file,
+    /// with the `ron` or `toml` feature respectively.
+    #[cfg(any(feature = "ron", feature = "toml"))]
+    pub fn from_file(path: impl Into<PathBuf>) -> Result<Self, StdbConfigError> {
+        let path = path.into();
+        let content = std::fs::read_to_string(&path).map_err(|source| StdbConfigError::Read {
+            path: path.clone(),
+            source: std::sync::Arc::new(source),
+        })?;
+
+        let parse_error = |message: String| StdbConfigError::Parse {
+            path: path.clone(),
+            message,
+        };
+        match path.extension().and_then(|ext| ext.to_str()) {
+            #[cfg(feature = "ron")]
+            Some("ron") => ron::from_str(&content).map_err(|err| parse_error(err.to_string())),
+            #[cfg(feature = "toml")]
+            Some("toml") => toml::from_str(&content).map_err(|err| parse_error(err.to_string())),
+            _ => Err(StdbConfigError::UnsupportedFormat { path }),
+        }
+    }
+
+    /// Returns this configuration with the settings set in `overrides` replacing its own.
+    ///
+    /// Useful to let environment variables override a configuration file:
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "toml")]
+    /// # fn load() -> Result<bevy_spacetimedb::StdbConfig, Box<dyn std::error::Error>> {
+    /// use bevy_spacetimedb::StdbConfig;
+    ///
+    /// // `from_env` reports all the invalid variables at once
+    /// let env = StdbConfig::from_env().map_err(|errors| format!("{errors:?}"))?;
+    /// let config = StdbConfig::from_file("stdb.toml")?.merge(env);
+    /// # Ok(config)
+    /// # }
+    /// ```
+    pub fn merge(self, overrides: StdbConfig) -> Self {
+        Self {
+            uri: overrides.uri.or(self.uri),
+            module_name: overrides.module_name.or(self.module_name),
+            token: overrides.token.or(self.token),
+            compression: overrides.compression.or(self.compression),
+            light_mode: overrides.light_mode.or(self.light_mode),
+        }
+    }
+
+    /// Checks that the configuration can be used to connect, reporting all the problems at once.
+    pub fn validate(&self) -> Result<(), Vec<StdbConfigError>> {
+        let mut errors = Vec::new();
+
+        match self.uri.as_deref() {
+            None | Some("") => errors.push(StdbConfigError::MissingUri),
+            Some(uri) => {
+                if let Err(err) = validate_uri(uri) {
+                    errors.push(err);
+                }
+            }
+        }
+        if self.module_name.as_deref().is_none_or(str::is_empty) {
+            errors.push(StdbConfigError::MissingModuleName);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// A problem with the configuration of the [`crate::StdbPlugin`].
+#[derive(Debug, Clone)]
+pub enum StdbConfigError {
+    /// No URI was set.
+    MissingUri,
+    /// No module name was set.
+    MissingModuleName,
+    /// No run function was set, see [`crate::StdbPlugin::with_run_fn`], [`crate::StdbPlugin::with_frame_tick`]
+    /// and [`crate::StdbPlugin::with_async_run_fn`].
+    MissingRunFn,
+    /// Bounded event channels use [`crate::BackpressurePolicy::Block`] with [`crate::StdbPlugin::with_frame_tick`],
+    /// which would freeze the app once a channel is full, as the channels are drained on the thread advancing the connection.
+    BlockingFrameTick,
+    /// The URI can't be used to connect to SpacetimeDB.
+    InvalidUri {
+        /// The invalid URI.
+        uri: String,
+        /// Why it is invalid.
+        reason: String,
+    },
+    /// A setting has a value that can't be parsed.
+    InvalidValue {
+        /// The name of the setting, or of the environment variable it was read from.
+        key: String,
+        /// The invalid value.
+        value: String,
+        /// A description of the expected values.
+        expected: &'static str,
+    },
+    /// The configuration file couldn't be read.
+    Read {
+        /// The path of the configuration file.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::sync::Arc<io::Error>,
+    },
+    /// The configuration file couldn't be parsed.
+    Parse {
+        /// The path of the configuration file.
+        path: PathBuf,
+        /// The parser's error message.
+        message: String,
+    },
+    /// The configuration file has an extension with no matching enabled feature.
+    UnsupportedFormat {
+        /// The path of the configuration file.
+        path: PathBuf,
+    },
+}
+
+impl StdbConfigError {
+    fn with_key(self, key: &str) -> Self {
+        match self {
+            Self::InvalidValue {
+                value, expected, ..
+            } => Self::InvalidValue {
+                key: key.to_string(),
+                value,
+                expected,
+            },
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for StdbConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingUri => write!(
+                f,
+                "no uri set, set it with with_uri() or the STDB_URI environment variable"
+            ),
+            Self::MissingModuleName => write!(
+                f,
+                "no module name set, set it with with_module_name() or the STDB_MODULE environment variable"
+            ),
+            Self::MissingRunFn => write!(
+                f,
+                "no run function set, set it with with_run_fn(), with_frame_tick(), with_async_run_fn() or create the plugin with StdbPlugin::new()"
+            ),
+            Self::BlockingFrameTick => write!(
+                f,
+                "bounded event channels can't block with with_frame_tick(), set with_backpressure() to DropNewest or DropOldest"
+            ),
+            Self::InvalidUri { uri, reason } => write!(f, "invalid uri `{uri}`: {reason}"),
+            Self::InvalidValue {
+                key,
+                value,
+                expected,
+            } => write!(f, "invalid value `{value}` for {key}, expected {expected}"),
+            Self::Read { path, source } => {
+                write!(f, "failed to read {}: {source}", path.display())
+            }
+            Self::Parse { path, message } => {
+                write!(f, "failed to parse {}: {message}", path.display())
+            }
+            Self::UnsupportedFormat { path } => write!(
+                f,
+                "unsupported configuration file {}, expected a .ron or .toml file with the matching feature enabled",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StdbConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+fn validate_uri(uri: &str) -> Result<(), StdbConfigError> {
+    let invalid = |reason: &str| StdbConfigError::InvalidUri {
+        uri: uri.to_string(),
+        reason: reason.to_string(),
+    };
+
+    let host = match uri.split_once("://") {
+        Some((scheme, rest)) => {
+            if !matches!(scheme, "http" | "https" | "ws" | "wss") {
+                return Err(invalid("the scheme must be one of http, https, ws or wss"));
+            }
+            rest
+        }
+        None => uri,
+    };
+    if host.is_empty() || host.starts_with('/') {
+        return Err(invalid("missing host"));
+    }
+    if host.contains(char::is_whitespace) {
+        return Err(invalid("contains whitespace"));
+    }
+
+    Ok(())
+}
+
+fn parse_compression(value: &str) -> Result<Compression, StdbConfigError> {
+    match value.to_ascii_lowercase().as_str() {
+        "none" => Ok(Compression::None),
+        "gzip" => Ok(Compression::Gzip),
+        "brotli" => Ok(Compression::Brotli),
+        _ => Err(StdbConfigError::InvalidValue {
+            key: "compression".to_string(),
+            value: value.to_string(),
+            expected: "one of none, gzip or brotli",
+        }),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, StdbConfigError> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(StdbConfigError::InvalidValue {
+            key: "light_mode".to_string(),
+            value: value.to_string(),
+            expected: "a boolean",
+        }),
+    }
+}
+
+#[cfg(any(feature = "ron", feature = "toml"))]
+fn deserialize_compression<'de, D>(deserializer: D) -> Result<Option<Compression>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| parse_compression(&value).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_vars(vars: &[(&str, &str)]) -> Result<StdbConfig, Vec<StdbConfigError>> {
+        StdbConfig::from_vars(|key| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    fn valid_config() -> StdbConfig {
+        StdbConfig {
+            uri: Some("https://maincloud.spacetimedb.com".to_string()),
+            module_name: Some("my_game".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn env_vars_are_parsed() {
+        let config = from_vars(&[
+            ("STDB_URI", "http://localhost:3000"),
+            ("STDB_MODULE", "my_game"),
+            ("STDB_TOKEN", "secret"),
+            ("STDB_COMPRESSION", "Brotli"),
+            ("STDB_LIGHT_MODE", "yes"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.uri.as_deref(), Some("http://localhost:3000"));
+        assert_eq!(config.module_name.as_deref(), Some("my_game"));
+        assert_eq!(config.token.as_deref(), Some("secret"));
+        assert!(matches!(config.compression, Some(Compression::Brotli)));
+        assert_eq!(config.light_mode, Some(true));
+    }
+
+    #[test]
+    fn unset_and_empty_env_vars_are_none() {
+        let config = from_vars(&[("STDB_URI", ""), ("STDB_LIGHT_MODE", "off")]).unwrap();
+
+        assert_eq!(config.uri, None);
+        assert_eq!(config.module_name, None);
+        assert_eq!(config.token, None);
+        assert!(config.compression.is_none());
+        assert_eq!(config.light_mode, Some(false));
+    }
+
+    #[test]
+    fn invalid_env_vars_are_all_reported() {
+        let errors =
+            from_vars(&[("STDB_COMPRESSION", "zstd"), ("STDB_LIGHT_MODE", "maybe")]).unwrap_err();
+
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                StdbConfigError::InvalidValue { key: compression, value: zstd, .. },
+                StdbConfigError::InvalidValue { key: light_mode, value: maybe, .. },
+            ] if compression == "STDB_COMPRESSION"
+                && zstd == "zstd"
+                && light_mode == "STDB_LIGHT_MODE"
+                && maybe == "maybe"
+        ));
+    }
+
+    #[test]
+    fn merge_prefers_overrides() {
+        let base = StdbConfig {
+            uri: Some("http://file".to_string()),
+            module_name: Some("file_module".to_string()),
+            token: Some("file_token".to_string()),
+            compression: Some(Compression::Gzip),
+            light_mode: Some(false),
+        };
+        let overrides = StdbConfig {
+            uri: Some("http://env".to_string()),
+            light_mode: Some(true),
+            ..Default::default()
+        };
+
+        let merged = base.merge(overrides);
+        assert_eq!(merged.uri.as_deref(), Some("http://env"));
+        assert_eq!(merged.module_name.as_deref(), Some("file_module"));
+        assert_eq!(merged.token.as_deref(), Some("file_token"));
+        assert!(matches!(merged.compression, Some(Compression::Gzip)));
+        assert_eq!(merged.light_mode, Some(true));
+    }
+
+    #[test]
+    fn validate_accepts_valid_config() {
+        valid_config().validate().unwrap();
+        StdbConfig {
+            uri: Some("localhost:3000".to_string()),
+            ..valid_config()
+        }
+        .validate()
+        .unwrap();
+    }
+
+    #[test]
+    fn validate_reports_missing_settings() {
+        let errors = StdbConfig::default().validate().unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                StdbConfigError::MissingUri,
+                StdbConfigError::MissingModuleName
+            ]
+        ));
+
+        let errors = StdbConfig {
+            uri: Some(String::new()),
+            module_name: Some(String::new()),
+            ..Default::default()
+        }
+        .validate()
+        .unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                StdbConfigError::MissingUri,
+                StdbConfigError::MissingModuleName
+            ]
+        ));
+    }
+
+    #[test]
+    fn validate_reports_invalid_uris() {
+        for uri in [
+            "ftp://localhost",
+            "http://",
+            "https:///my_game",
+            "http://local host",
+        ] {
+            let errors = StdbConfig {
+                uri: Some(uri.to_string()),
+                ..valid_config()
+            }
+            .validate()
+            .unwrap_err();
+            assert!(
+                matches!(errors.as_slice(), [StdbConfigError::InvalidUri { uri: invalid, .. }] if invalid == uri),
+                "{uri}: {errors:?}"
+            );
+        }
+    }
+
+    #[cfg(any(feature = "ron", feature = "toml"))]
+    fn write_file(dir: &tempfile::TempDir, name: &str, content: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[cfg(feature = "ron")]
+    #[test]
+    fn ron_file_is_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(
+            &dir,
+            "stdb.ron",
+            r#"(uri: Some("http://localhost:3000"), compression: Some("gzip"), light_mode: Some(true))"#,
+        );
+
+        let config = StdbConfig::from_file(path).unwrap();
+        assert_eq!(config.uri.as_deref(), Some("http://localhost:3000"));
+        assert_eq!(config.module_name, None);
+        assert!(matches!(config.compression, Some(Compression::Gzip)));
+        assert_eq!(config.light_mode, Some(true));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_file_is_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(
+            &dir,
+            "stdb.toml",
+            "uri = \"http://localhost:3000\"\nmodule_name = \"my_game\"\ncompression = \"none\"\n",
+        );
+
+        let config = StdbConfig::from_file(path).unwrap();
+        assert_eq!(config.uri.as_deref(), Some("http://localhost:3000"));
+        assert_eq!(config.module_name.as_deref(), Some("my_game"));
+        assert!(matches!(config.compression, Some(Compression::None)));
+        assert_eq!(config.light_mode, None);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn invalid_files_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let path = write_file(&dir, "unknown.toml", "port = 3000\n");
+        assert!(matches!(
+            StdbConfig::from_file(path),
+            Err(StdbConfigError::Parse { .. })
+        ));
+
+        let path = write_file(&dir, "compression.toml", "compression = \"zstd\"\n");
+        assert!(matches!(
+            StdbConfig::from_file(path),
+            Err(StdbConfigError::Parse { .. })
+        ));
+
+        let path = write_file(&dir, "stdb.json", "{}");
+        assert!(matches!(
+            StdbConfig::from_file(path),
+            Err(StdbConfigError::UnsupportedFormat { .. })
+        ));
+
+        assert!(matches!(
+            StdbConfig::from_file(dir.path().join("missing.toml")),
+            Err(StdbConfigError::Read { .. })
+        ));
+    }
+}
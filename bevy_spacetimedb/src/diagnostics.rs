@@ -0,0 +1,403 @@
+use std::{
+    any::TypeId,
+    collections::VecDeque,
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, RegisterDiagnostic},
+    ecs::schedule::InternedScheduleLabel,
+    platform::{collections::HashMap, time::Instant},
+    prelude::*,
+};
+use spacetimedb_sdk::__codegen as spacetime_codegen;
+
+use crate::{
+    ReducerResultEvent, RegisterableReducerEvent, StdbBacklog, StdbConnectedEvent, StdbConnection,
+    StdbDisconnectedEvent, StdbMessageCounts, StdbSet,
+};
+
+/// Registers Bevy [`Diagnostic`]s about the SpacetimeDB connection and its event channels,
+/// to be added along with the [`crate::StdbPlugin`].
+///
+/// - [`Self::UPTIME`]: the seconds since the connection was established, 0 while disconnected
+/// - [`Self::CONNECTIONS`]: the number of times the connection was established, counting the reconnections
+/// - [`Self::REDUCER_LATENCY`]: the milliseconds between a reducer call made with
+///   [`StdbConnection::call_reducer`] and its result being read by the app, for the reducers registered with `add_reducer`
+/// - [`Self::BACKLOG`]: the number of messages waiting in the event channels, see [`StdbBacklog`]
+/// - `stdb/messages/<message>` and `stdb/backlog/<message>`: the messages drained per second and waiting
+///   per message type, e.g. `stdb/messages/InsertEvent<Player>`, see [`Self::messages_path`] and [`Self::backlog_path`]
+#[derive(Debug, Clone, Default)]
+pub struct StdbDiagnosticsPlugin {
+    overlay: bool,
+}
+
+impl StdbDiagnosticsPlugin {
+    /// The seconds since the connection was established.
+    pub const UPTIME: DiagnosticPath = DiagnosticPath::const_new("stdb/uptime");
+    /// The number of times the connection was established.
+    pub const CONNECTIONS: DiagnosticPath = DiagnosticPath::const_new("stdb/connections");
+    /// The milliseconds between a reducer call made with [`StdbConnection::call_reducer`] and its
+    /// [`ReducerResultEvent`] being read by the app, including the frame it waited to be drained.
+    ///
+    /// Only the results of the calls made by this client are measured, recognized by their caller connection id.
+    pub const REDUCER_LATENCY: DiagnosticPath = DiagnosticPath::const_new("stdb/reducer_latency");
+    /// The number of messages waiting in the event channels.
+    pub const BACKLOG: DiagnosticPath = DiagnosticPath::const_new("stdb/backlog");
+
+    /// Shows the diagnostics in a text overlay at the top right corner of the window.
+    pub fn with_overlay(mut self) -> Self {
+        self.overlay = true;
+        self
+    }
+
+    /// The path of the diagnostic of the messages of `T` drained per second.
+    pub fn messages_path<T: Message>() -> DiagnosticPath {
+        channel_path("messages", std::any::type_name::<T>())
+    }
+
+    /// The path of the diagnostic of the messages of `T` waiting in their event channel.
+    pub fn backlog_path<T: Message>() -> DiagnosticPath {
+        channel_path("backlog", std::any::type_name::<T>())
+    }
+}
+
+impl Plugin for StdbDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConnectionStats>()
+            .register_diagnostic(Diagnostic::new(Self::UPTIME).with_suffix("s"))
+            .register_diagnostic(Diagnostic::new(Self::CONNECTIONS))
+            .register_diagnostic(Diagnostic::new(Self::REDUCER_LATENCY).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::BACKLOG))
+            .add_systems(Update, (measure_connection, measure_channels));
+
+        // The reducers registered before this plugin was added
+        if let Some(pending) = app
+            .world_mut()
+            .remove_resource::<PendingReducerDiagnostics>()
+        {
+            for add_systems in pending.0 {
+                add_systems(app);
+            }
+        }
+
+        if self.overlay {
+            app.add_systems(Startup, spawn_overlay)
+                .add_systems(Update, update_overlay.after(measure_channels));
+        }
+    }
+}
+
+fn channel_path(kind: &str, type_name: &str) -> DiagnosticPath {
+    DiagnosticPath::new(format!("stdb/{kind}/{}", ShortName(type_name)))
+}
+
+#[derive(Resource, Default)]
+struct ConnectionStats {
+    connected_at: Option<Instant>,
+    connections: usize,
+}
+
+fn measure_connection(
+    mut connected: MessageReader<StdbConnectedEvent>,
+    mut disconnected: MessageReader<StdbDisconnectedEvent>,
+    mut stats: ResMut<ConnectionStats>,
+    mut diagnostics: Diagnostics,
+) {
+    let connections = connected.read().count();
+    if connections > 0 {
+        stats.connected_at = Some(Instant::now());
+        stats.connections += connections;
+    }
+    if disconnected.read().count() > 0 {
+        stats.connected_at = None;
+    }
+
+    diagnostics.add_measurement(&StdbDiagnosticsPlugin::UPTIME, || {
+        stats
+            .connected_at
+            .map_or(0.0, |connected_at| connected_at.elapsed().as_secs_f64())
+    });
+    diagnostics.add_measurement(&StdbDiagnosticsPlugin::CONNECTIONS, || {
+        stats.connections as f64
+    });
+}
+
+/// The diagnostics of an event channel, with the count of messages drained at the last measurement.
+struct ChannelDiagnostics {
+    messages: DiagnosticPath,
+    backlog: DiagnosticPath,
+    count: usize,
+}
+
+fn measure_channels(
+    counts: Option<Res<StdbMessageCounts>>,
+    backlog: Option<Res<StdbBacklog>>,
+    mut store: ResMut<DiagnosticsStore>,
+    mut channels: Local<HashMap<&'static str, ChannelDiagnostics>>,
+    mut last_measurement: Local<Option<Instant>>,
+) {
+    let (Some(counts), Some(backlog)) = (counts, backlog) else {
+        return;
+    };
+    let now = Instant::now();
+    let elapsed = last_measurement
+        .replace(now)
+        .map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+
+    for (type_name, count) in counts.iter() {
+        let channel = channels.entry(type_name).or_insert_with(|| {
+            let channel = ChannelDiagnostics {
+                messages: channel_path("messages", type_name),
+                backlog: channel_path("backlog", type_name),
+                count,
+            };
+            store.add(Diagnostic::new(channel.messages.clone()).with_suffix("/s"));
+            store.add(Diagnostic::new(channel.backlog.clone()));
+            channel
+        });
+
+        let drained = count - channel.count;
+        channel.count = count;
+        if elapsed > 0.0
+            && let Some(diagnostic) = store.get_mut(&channel.messages)
+        {
+            diagnostic.add_measurement(measurement(now, drained as f64 / elapsed));
+        }
+    }
+
+    for (type_name, len) in backlog.iter() {
+        if let Some(diagnostic) = channels
+            .get(type_name)
+            .and_then(|channel| store.get_mut(&channel.backlog))
+        {
+            diagnostic.add_measurement(measurement(now, len as f64));
+        }
+    }
+    if let Some(diagnostic) = store.get_mut(&StdbDiagnosticsPlugin::BACKLOG) {
+        diagnostic.add_measurement(measurement(now, backlog.total() as f64));
+    }
+}
+
+fn measurement(time: Instant, value: f64) -> bevy::diagnostic::DiagnosticMeasurement {
+    bevy::diagnostic::DiagnosticMeasurement { time, value }
+}
+
+/// Adds the systems measuring the events of a reducer.
+type AddReducerSystems = Box<dyn FnOnce(&mut App) + Send + Sync>;
+
+/// The systems measuring the reducer events registered before the [`StdbDiagnosticsPlugin`] was added.
+#[derive(Resource, Default)]
+struct PendingReducerDiagnostics(Vec<AddReducerSystems>);
+
+/// Measures the latency of the reducer events <E> drained in `schedule`, if the [`StdbDiagnosticsPlugin`] is added
+/// before or after the reducer is registered.
+pub(crate) fn add_reducer_diagnostics<C, M, E>(app: &mut App, schedule: InternedScheduleLabel)
+where
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext + Sync,
+    M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
+    E: RegisterableReducerEvent<C, M> + Send + Sync + 'static,
+{
+    let add_systems = move |app: &mut App| {
+        app.add_systems(
+            schedule,
+            measure_reducer_latency::<C, M, E>.in_set(StdbSet::Dispatch),
+        );
+    };
+    if app.is_plugin_added::<StdbDiagnosticsPlugin>() {
+        add_systems(app);
+    } else {
+        app.world_mut()
+            .get_resource_or_init::<PendingReducerDiagnostics>()
+            .0
+            .push(Box::new(add_systems));
+    }
+}
+
+/// Measures the latency of the results of the calls of <E> read this frame,
+/// see [`StdbDiagnosticsPlugin::REDUCER_LATENCY`].
+fn measure_reducer_latency<C, M, E>(
+    mut events: MessageReader<ReducerResultEvent<E>>,
+    stdb: Option<Res<StdbConnection<C>>>,
+    mut store: ResMut<DiagnosticsStore>,
+) where
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext + Sync,
+    M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
+    E: RegisterableReducerEvent<C, M> + Send + Sync + 'static,
+{
+    // The calls of this client can't be recognized until it is connected
+    let (Some(stdb), Some(diagnostic)) =
+        (stdb, store.get_mut(&StdbDiagnosticsPlugin::REDUCER_LATENCY))
+    else {
+        events.clear();
+        return;
+    };
+    let Some(connection_id) = stdb.try_connection_id() else {
+        events.clear();
+        return;
+    };
+
+    let now = Instant::now();
+    for event in events.read() {
+        let Some(reducer_event) = event.result.reducer_event() else {
+            continue;
+        };
+        if reducer_event.caller_connection_id != Some(connection_id) {
+            continue;
+        }
+        if let Some(called_at) = stdb.reducer_calls().take::<E>() {
+            let latency = now.duration_since(called_at).as_secs_f64() * 1000.0;
+            diagnostic.add_measurement(measurement(now, latency));
+        }
+    }
+}
+
+/// The times of the reducer calls made with [`StdbConnection::call_reducer`] waiting for their result,
+/// per reducer event type, in the order of the calls.
+#[derive(Default)]
+pub(crate) struct ReducerCalls(Arc<Mutex<HashMap<TypeId, VecDeque<Instant>>>>);
+
+impl ReducerCalls {
+    /// The calls kept per reducer, so they don't pile up when their results aren't measured.
+    const MAX_PENDING: usize = 64;
+
+    /// Records a call of the reducer <E> made now.
+    pub(crate) fn record<E: 'static>(&self) {
+        let mut calls = self.0.lock().unwrap();
+        let pending = calls.entry(TypeId::of::<E>()).or_default();
+        if pending.len() == Self::MAX_PENDING {
+            pending.pop_front();
+        }
+        pending.push_back(Instant::now());
+    }
+
+    /// Forgets the last call of the reducer <E>, which failed to be sent.
+    pub(crate) fn cancel<E: 'static>(&self) {
+        if let Some(pending) = self.0.lock().unwrap().get_mut(&TypeId::of::<E>()) {
+            pending.pop_back();
+        }
+    }
+
+    /// Takes the time of the oldest call of the reducer <E> waiting for its result.
+    pub(crate) fn take<E: 'static>(&self) -> Option<Instant> {
+        self.0
+            .lock()
+            .unwrap()
+            .get_mut(&TypeId::of::<E>())?
+            .pop_front()
+    }
+}
+
+/// The text of the diagnostics overlay.
+#[derive(Component)]
+struct StdbDiagnosticsOverlay;
+
+fn spawn_overlay(mut commands: Commands) {
+    commands.spawn((
+        StdbDiagnosticsOverlay,
+        Text::default(),
+        TextFont::from_font_size(14.0),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            ..default()
+        },
+        GlobalZIndex(i32::MAX),
+    ));
+}
+
+fn update_overlay(
+    store: Res<DiagnosticsStore>,
+    mut overlay: Single<&mut Text, With<StdbDiagnosticsOverlay>>,
+) {
+    let mut text = String::from("SpacetimeDB");
+    for diagnostic in store.iter() {
+        if !diagnostic.path().as_str().starts_with("stdb/") {
+            continue;
+        }
+        let Some(value) = diagnostic.smoothed() else {
+            continue;
+        };
+        let _ = write!(
+            text,
+            "\n{}: {value:.1}{}",
+            &diagnostic.path().as_str()["stdb/".len()..],
+            diagnostic.suffix
+        );
+    }
+    overlay.0 = text;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::diagnostic::DiagnosticsPlugin;
+    use spacetimedb_sdk::Identity;
+
+    use super::*;
+
+    struct SetName;
+    struct SetColor;
+
+    #[test]
+    fn reducer_calls_are_taken_in_order_per_reducer() {
+        let calls = ReducerCalls::default();
+        calls.record::<SetName>();
+        let first = calls.0.lock().unwrap()[&TypeId::of::<SetName>()][0];
+        calls.record::<SetName>();
+        calls.record::<SetColor>();
+
+        assert_eq!(calls.take::<SetName>(), Some(first));
+        assert!(calls.take::<SetName>().is_some());
+        assert_eq!(calls.take::<SetName>(), None);
+        assert!(calls.take::<SetColor>().is_some());
+    }
+
+    #[test]
+    fn cancelled_and_overflowing_reducer_calls_are_forgotten() {
+        let calls = ReducerCalls::default();
+        calls.record::<SetName>();
+        calls.cancel::<SetName>();
+        assert_eq!(calls.take::<SetName>(), None);
+
+        for _ in 0..ReducerCalls::MAX_PENDING + 10 {
+            calls.record::<SetName>();
+        }
+        let mut taken = 0;
+        while calls.take::<SetName>().is_some() {
+            taken += 1;
+        }
+        assert_eq!(taken, ReducerCalls::MAX_PENDING);
+    }
+
+    #[test]
+    fn connections_are_counted() {
+        let mut app = App::new();
+        app.add_plugins((DiagnosticsPlugin, StdbDiagnosticsPlugin::default()))
+            .add_message::<StdbConnectedEvent>()
+            .add_message::<StdbDisconnectedEvent>();
+        let connect = |app: &mut App| {
+            app.world_mut().write_message(StdbConnectedEvent {
+                identity: Identity::ZERO,
+                access_token: String::new(),
+            });
+            app.update();
+        };
+        let connections = |app: &App| {
+            app.world()
+                .resource::<DiagnosticsStore>()
+                .get(&StdbDiagnosticsPlugin::CONNECTIONS)
+                .and_then(Diagnostic::value)
+        };
+
+        app.update();
+        assert_eq!(connections(&app), Some(0.0));
+        connect(&mut app);
+        app.world_mut()
+            .write_message(StdbDisconnectedEvent { err: None });
+        app.update();
+        connect(&mut app);
+        assert_eq!(connections(&app), Some(2.0));
+    }
+}
@@ -1,5 +1,5 @@
-use bevy::prelude::{Event, Message};
-use spacetimedb_sdk::{Error, Identity};
+use bevy::prelude::{Entity, EntityEvent, Event, Message};
+use spacetimedb_sdk::{Error, Identity, ReducerEvent, Status};
 
 /// An event that is triggered when a connection to SpacetimeDB is established.
 #[derive(Message)]
@@ -24,22 +24,30 @@ pub struct StdbConnectionErrorEvent {
     pub err: Error,
 }
 
+/// A message written once a subscription named with [`crate::StdbSubscriptions::on_snapshot_ready`] is applied,
+/// after the messages of its initial rows, e.g. their [`InsertEvent`]s, were written.
+#[derive(Message, Event, Clone, Debug)]
+pub struct SubscriptionSnapshotReady {
+    /// The name of the subscription.
+    pub name: String,
+}
+
 /// An event that is triggered when a row is inserted into a table.
-#[derive(Message)]
+#[derive(Message, Event, Clone)]
 pub struct InsertEvent<T> {
     /// The row that was inserted.
     pub row: T,
 }
 
 /// An event that is triggered when a row is deleted from a table.
-#[derive(Message)]
+#[derive(Message, Event, Clone)]
 pub struct DeleteEvent<T> {
     /// The row that was deleted.
     pub row: T,
 }
 
 /// An event that is triggered when a row is updated in a table.
-#[derive(Message)]
+#[derive(Message, Event, Clone)]
 pub struct UpdateEvent<T> {
     /// The old row.
     pub old: T,
@@ -48,7 +56,7 @@ pub struct UpdateEvent<T> {
 }
 
 /// An event that is triggered when a row is inserted or updated in a table.
-#[derive(Message)]
+#[derive(Message, Event, Clone)]
 pub struct InsertUpdateEvent<T> {
     /// The previous value of the row if it was updated.
     pub old: Option<T>,
@@ -56,8 +64,45 @@ pub struct InsertUpdateEvent<T> {
     pub new: T,
 }
 
+/// An event that is triggered when a row of a mirrored table changes, in the order of the changes,
+/// registered with [`crate::StdbPlugin::add_mirrored_table`].
+#[derive(Message, Event, Clone)]
+pub enum RowChangeEvent<T> {
+    /// The row was inserted.
+    Insert(T),
+    /// The row was updated.
+    Update {
+        /// The old row.
+        old: T,
+        /// The new row.
+        new: T,
+    },
+    /// The row was deleted.
+    Delete(T),
+}
+
+/// A table or reducer event triggered on the entity mirroring its row,
+/// when the events are triggered with [`crate::StdbPlugin::with_triggered_events`].
+///
+/// The table events of a mirrored table are triggered as the row changes are applied to the entity, in
+/// [`crate::StdbSet::ApplyMirror`]: after its components are inserted or updated, and before it is despawned.
+/// Reducer events are targeted with [`crate::StdbPlugin::add_observed_reducer_on`].
+///
+/// ```ignore
+/// commands.entity(player).observe(|event: On<StdbEntityEvent<DeleteEvent<Player>>>| {
+///     info!("{} left", event.event.row.name);
+/// });
+/// ```
+#[derive(EntityEvent, Clone)]
+pub struct StdbEntityEvent<E> {
+    /// The entity mirroring the row.
+    pub entity: Entity,
+    /// The table or reducer event.
+    pub event: E,
+}
+
 /// An event that is triggered when a reducer is invoked.
-#[derive(Message, Debug)]
+#[derive(Message, Event, Clone, Debug)]
 pub struct ReducerResultEvent<T> {
     /// The result of the reducer invocation.
     pub result: T,
@@ -69,3 +114,28 @@ impl<T> ReducerResultEvent<T> {
         Self { result }
     }
 }
+
+/// An event that is triggered when any reducer of the module is invoked,
+/// registered with [`crate::StdbPlugin::add_any_reducer_event`].
+#[derive(Message, Event, Clone, Debug)]
+pub struct AnyReducerEvent<R> {
+    /// The reducer event, holding the invoked reducer with its arguments.
+    pub event: ReducerEvent<R>,
+}
+
+impl<R> AnyReducerEvent<R> {
+    /// Creates a new reducer event.
+    pub fn new(event: ReducerEvent<R>) -> Self {
+        Self { event }
+    }
+
+    /// The invoked reducer, a variant of the module's `Reducer` enum holding its arguments.
+    pub fn reducer(&self) -> &R {
+        &self.event.reducer
+    }
+
+    /// Whether the reducer committed, failed or ran out of energy.
+    pub fn status(&self) -> &Status {
+        &self.event.status
+    }
+}
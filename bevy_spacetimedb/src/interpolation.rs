@@ -0,0 +1,316 @@
+use std::{collections::VecDeque, marker::PhantomData, time::Duration};
+
+use bevy::prelude::*;
+
+use crate::{MirroredRow, StdbRow, StdbSet, mirror::mirror_schedule};
+
+/// A value that can be interpolated between two updates of a row.
+pub trait Interpolate {
+    /// The value at `t` between `from` (0) and `to` (1), `t` is greater than 1 when extrapolating.
+    fn interpolate(from: &Self, to: &Self, t: f64) -> Self;
+}
+
+macro_rules! impl_interpolate_float {
+    ($($float:ty),*) => {
+        $(impl Interpolate for $float {
+            fn interpolate(from: &Self, to: &Self, t: f64) -> Self {
+                from + (to - from) * t as $float
+            }
+        })*
+    };
+}
+
+/// 1 in the 32.32 fixed point used to interpolate integers.
+const FIXED_ONE: i128 = 1 << 32;
+
+macro_rules! impl_interpolate_int {
+    ($($int:ty),*) => {
+        $(impl Interpolate for $int {
+            fn interpolate(from: &Self, to: &Self, t: f64) -> Self {
+                // In fixed point, so 64-bit values keep their precision
+                let t = (t * FIXED_ONE as f64) as i128;
+                let offset = (*to as i128 - *from as i128)
+                    .saturating_mul(t)
+                    .saturating_add(FIXED_ONE / 2)
+                    >> 32;
+                (*from as i128)
+                    .saturating_add(offset)
+                    .clamp(<$int>::MIN as i128, <$int>::MAX as i128) as $int
+            }
+        })*
+    };
+}
+
+impl_interpolate_float!(f32, f64);
+impl_interpolate_int!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+/// A mirrored row whose fields can be smoothed between its updates with [`StdbInterpolated`],
+/// implemented with [`impl_interpolated_row!`](crate::impl_interpolated_row).
+pub trait InterpolatedRow: MirroredRow {
+    /// The row at `t` between `from` (0) and `to` (1), with the fields that are not interpolated of `to`.
+    fn interpolate(from: &Self, to: &Self, t: f64) -> Self;
+}
+
+/// Implements [`InterpolatedRow`] for a row, interpolating the listed fields.
+///
+/// ```ignore
+/// bevy_spacetimedb::impl_interpolated_row!(StarSystem { x, y, z });
+/// ```
+#[macro_export]
+macro_rules! impl_interpolated_row {
+    ($row:ty { $($field:ident),+ $(,)? }) => {
+        impl $crate::InterpolatedRow for $row {
+            fn interpolate(from: &Self, to: &Self, t: f64) -> Self {
+                let mut row = ::std::clone::Clone::clone(to);
+                $(row.$field = $crate::Interpolate::interpolate(&from.$field, &to.$field, t);)+
+                row
+            }
+        }
+    };
+}
+
+/// Smooths the [`StdbRow`] of a mirrored entity between its updates, inserted on the entities to smooth.
+///
+/// The row is shown [`Self::delay`] in the past, interpolating between the updates received around that time.
+/// When the next update is late, the row is extrapolated from the last two updates for at most
+/// [`Self::max_extrapolation`]. The systems are added by the [`StdbInterpolationPlugin`] of the row.
+#[derive(Component)]
+pub struct StdbInterpolated<TRow: InterpolatedRow> {
+    /// How far in the past the row is shown, usually a bit more than the interval between updates.
+    pub delay: Duration,
+    /// How long the row keeps moving past the last update when the next one is late.
+    pub max_extrapolation: Duration,
+    /// The rows received with the time they were received at, oldest first.
+    snapshots: VecDeque<(Duration, TRow)>,
+    value: Option<TRow>,
+}
+
+impl<TRow: InterpolatedRow> StdbInterpolated<TRow> {
+    /// Shows the row `delay` in the past, without extrapolating.
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            max_extrapolation: Duration::ZERO,
+            snapshots: VecDeque::new(),
+            value: None,
+        }
+    }
+
+    /// Extrapolates the row for at most `max_extrapolation` when an update is late.
+    pub fn with_max_extrapolation(mut self, max_extrapolation: Duration) -> Self {
+        self.max_extrapolation = max_extrapolation;
+        self
+    }
+
+    /// The smoothed row, `None` until the row is received.
+    pub fn value(&self) -> Option<&TRow> {
+        self.value.as_ref()
+    }
+
+    /// Computes the row shown at `now`, dropping the rows that are no longer needed.
+    fn sample(&mut self, now: Duration) {
+        let render_time = now.saturating_sub(self.delay);
+        // Keep the last row received before the render time
+        while self.snapshots.len() > 2 && self.snapshots[1].0 <= render_time {
+            self.snapshots.pop_front();
+        }
+
+        self.value = match (self.snapshots.front(), self.snapshots.get(1)) {
+            (None, _) => None,
+            (Some((_, row)), None) => Some(row.clone()),
+            (Some((from_time, from)), Some((to_time, to))) => {
+                let render_time = render_time
+                    .max(*from_time)
+                    .min(*to_time + self.max_extrapolation);
+                let interval = (*to_time - *from_time).as_secs_f64();
+                let t = if interval > 0.0 {
+                    (render_time - *from_time).as_secs_f64() / interval
+                } else {
+                    1.0
+                };
+                Some(TRow::interpolate(from, to, t))
+            }
+        };
+    }
+}
+
+/// Adds the systems of [`StdbInterpolated<TRow>`], which run after [`StdbSet::ApplyMirror`]
+/// in the schedule draining the rows, `PreUpdate` by default.
+pub struct StdbInterpolationPlugin<TRow: InterpolatedRow>(PhantomData<TRow>);
+
+impl<TRow: InterpolatedRow> Default for StdbInterpolationPlugin<TRow> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<TRow: InterpolatedRow> Plugin for StdbInterpolationPlugin<TRow> {
+    fn build(&self, _app: &mut App) {}
+
+    // The drain schedule is known once the StdbPlugin is built
+    fn finish(&self, app: &mut App) {
+        let schedule = mirror_schedule::<TRow>(app);
+        app.add_systems(
+            schedule,
+            (record_rows::<TRow>, sample_rows::<TRow>)
+                .chain()
+                .after(StdbSet::ApplyMirror),
+        );
+    }
+}
+
+/// The rows updated since the last frame, or whose interpolation was just enabled.
+type ReceivedRows<TRow> = Or<(Changed<StdbRow<TRow>>, Added<StdbInterpolated<TRow>>)>;
+
+/// Records the rows received since the last frame.
+fn record_rows<TRow: InterpolatedRow>(
+    time: Res<Time<Real>>,
+    mut rows: Query<(&StdbRow<TRow>, &mut StdbInterpolated<TRow>), ReceivedRows<TRow>>,
+) {
+    let now = time.elapsed();
+    for (row, mut interpolated) in &mut rows {
+        // A row updated twice in a frame replaces the previous one
+        if interpolated
+            .snapshots
+            .back()
+            .is_some_and(|(time, _)| *time == now)
+        {
+            interpolated.snapshots.pop_back();
+        }
+        interpolated.snapshots.push_back((now, row.0.clone()));
+    }
+}
+
+fn sample_rows<TRow: InterpolatedRow>(
+    time: Res<Time<Real>>,
+    mut rows: Query<&mut StdbInterpolated<TRow>>,
+) {
+    let now = time.elapsed();
+    for mut interpolated in &mut rows {
+        interpolated.sample(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Ship {
+        id: u32,
+        x: f64,
+    }
+
+    impl MirroredRow for Ship {
+        type Key = u32;
+
+        fn key(&self) -> u32 {
+            self.id
+        }
+    }
+
+    crate::impl_interpolated_row!(Ship { x });
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    /// The ship interpolated 1s in the past, received at each of `snapshots` (time, x).
+    fn interpolated(snapshots: &[(f64, f64)]) -> StdbInterpolated<Ship> {
+        let mut interpolated = StdbInterpolated::new(secs(1.0));
+        interpolated.snapshots = snapshots
+            .iter()
+            .map(|&(time, x)| (secs(time), Ship { id: 1, x }))
+            .collect();
+        interpolated
+    }
+
+    fn sample_x(interpolated: &mut StdbInterpolated<Ship>, now: f64) -> Option<f64> {
+        interpolated.sample(secs(now));
+        interpolated.value().map(|ship| ship.x)
+    }
+
+    #[test]
+    fn a_single_row_is_shown_as_is() {
+        let mut ship = interpolated(&[]);
+        assert_eq!(sample_x(&mut ship, 1.0), None);
+
+        let mut ship = interpolated(&[(1.0, 5.0)]);
+        assert_eq!(sample_x(&mut ship, 0.5), Some(5.0));
+        assert_eq!(sample_x(&mut ship, 10.0), Some(5.0));
+    }
+
+    #[test]
+    fn rows_are_interpolated_in_the_past() {
+        let mut ship = interpolated(&[(1.0, 0.0), (2.0, 10.0)]);
+        // Before the first row
+        assert_eq!(sample_x(&mut ship, 1.5), Some(0.0));
+        assert_eq!(sample_x(&mut ship, 2.25), Some(2.5));
+        assert_eq!(sample_x(&mut ship, 3.0), Some(10.0));
+    }
+
+    #[test]
+    fn extrapolation_is_capped() {
+        let mut ship = interpolated(&[(1.0, 0.0), (2.0, 10.0)]);
+        assert_eq!(sample_x(&mut ship, 4.0), Some(10.0));
+
+        let mut ship = ship.with_max_extrapolation(secs(0.5));
+        assert_eq!(sample_x(&mut ship, 3.25), Some(12.5));
+        assert_eq!(sample_x(&mut ship, 4.0), Some(15.0));
+        assert_eq!(sample_x(&mut ship, 100.0), Some(15.0));
+    }
+
+    #[test]
+    fn rows_received_at_the_same_time_show_the_last_one() {
+        let mut ship = interpolated(&[(1.0, 0.0), (1.0, 10.0)]).with_max_extrapolation(secs(1.0));
+        assert_eq!(sample_x(&mut ship, 1.0), Some(10.0));
+        assert_eq!(sample_x(&mut ship, 3.0), Some(10.0));
+    }
+
+    #[test]
+    fn rows_older_than_the_render_time_are_dropped() {
+        let mut ship = interpolated(&[(1.0, 0.0), (2.0, 10.0), (3.0, 20.0), (4.0, 30.0)]);
+        assert_eq!(sample_x(&mut ship, 2.5), Some(5.0));
+        assert_eq!(ship.snapshots.len(), 4);
+
+        assert_eq!(sample_x(&mut ship, 3.5), Some(15.0));
+        assert_eq!(
+            ship.snapshots.front().map(|(time, _)| *time),
+            Some(secs(2.0))
+        );
+        assert_eq!(ship.snapshots.len(), 3);
+
+        // The last two rows are kept to extrapolate
+        assert_eq!(sample_x(&mut ship, 10.0), Some(30.0));
+        assert_eq!(ship.snapshots.len(), 2);
+    }
+
+    #[test]
+    fn integers_are_interpolated() {
+        assert_eq!(i32::interpolate(&-10, &10, 0.0), -10);
+        assert_eq!(i32::interpolate(&-10, &10, 0.25), -5);
+        assert_eq!(i32::interpolate(&-10, &10, 1.0), 10);
+        assert_eq!(u8::interpolate(&0, &3, 0.5), 2);
+        // Extrapolated
+        assert_eq!(i32::interpolate(&-10, &10, 1.5), 20);
+    }
+
+    #[test]
+    fn large_64_bit_integers_keep_their_precision() {
+        let from = u64::MAX - 10;
+        assert_eq!(u64::interpolate(&from, &u64::MAX, 0.0), from);
+        assert_eq!(u64::interpolate(&from, &u64::MAX, 0.5), u64::MAX - 5);
+        assert_eq!(u64::interpolate(&from, &u64::MAX, 1.0), u64::MAX);
+
+        let from = (1 << 60) + 1;
+        assert_eq!(i64::interpolate(&from, &(from + 4), 0.25), from + 1);
+        assert_eq!(i64::interpolate(&i64::MIN, &i64::MAX, 1.0), i64::MAX);
+    }
+
+    #[test]
+    fn extrapolated_integers_saturate() {
+        assert_eq!(u64::interpolate(&(u64::MAX - 10), &u64::MAX, 2.0), u64::MAX);
+        assert_eq!(i8::interpolate(&0, &-100, 2.0), i8::MIN);
+        assert_eq!(u32::interpolate(&0, &10, f64::MAX), u32::MAX);
+    }
+}
@@ -3,18 +3,56 @@
 //! A bevy plugin for SpacetimeDB.
 
 mod aliases;
+mod cache;
+mod callbacks;
 mod channel_receiver;
+mod config;
+mod diagnostics;
 mod events;
+mod interpolation;
+mod mirror;
 mod plugin;
+mod prediction;
 mod reducers;
+mod reflect;
+mod registry;
+mod relationships;
+mod runner;
 mod stdb_connection;
+mod subscriptions;
 mod tables;
+mod token_store;
 
 pub use aliases::*;
 pub use bevy_spacetimedb_macros::*;
-pub use channel_receiver::AddEventChannelAppExtensions;
+pub use cache::StdbTable;
+pub use channel_receiver::{
+    AddEventChannelAppExtensions, BackpressurePolicy, StdbBacklog, StdbChannelConfig,
+    StdbEventBudget, StdbMessageCounts, StdbSender, StdbSet, stdb_backlog_empty,
+};
+pub use config::{StdbConfig, StdbConfigError};
+/// The channels taken by [`AddEventChannelAppExtensions::add_event_channel`].
+pub use crossbeam_channel;
+pub use diagnostics::StdbDiagnosticsPlugin;
 pub use events::*;
+pub use interpolation::{Interpolate, InterpolatedRow, StdbInterpolated, StdbInterpolationPlugin};
+pub use mirror::{MirroredRow, StdbMirror, StdbRow, StdbRowMapper};
 pub use plugin::*;
-pub use reducers::RegisterableReducerEvent;
+pub use prediction::{StdbPredictionExtensions, StdbPredictions};
+pub use reducers::{
+    AnyReducerCallbacks, ReducerEventConnection, RegisterableReducerEvent, reducer_callback_span,
+};
+pub use reflect::StdbReflectDefault;
+pub use registry::{StdbAppExtensions, StdbCallbackRegistry, StdbCommandsExtensions};
+pub use runner::{RunnableConnection, StdbConnectionTask};
 pub use stdb_connection::*;
+pub use subscriptions::{StdbSubscriptions, stdb_snapshot_ready, stdb_snapshots_ready};
 pub use tables::TableEvents;
+pub use token_store::*;
+
+/// The crates used by the code generated by the macros, so it doesn't depend on the user's dependencies.
+#[doc(hidden)]
+pub mod __private {
+    pub use bevy;
+    pub use spacetimedb_sdk;
+}
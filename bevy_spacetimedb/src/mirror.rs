@@ -0,0 +1,563 @@
+use std::{hash::Hash, marker::PhantomData, sync::Arc};
+
+use bevy::{
+    app::PreUpdate,
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use spacetimedb_sdk::{__codegen as spacetime_codegen, Table, TableWithPrimaryKey};
+
+use crate::{
+    DeleteEvent, InsertEvent, RowChangeEvent, StdbChannelConfig, StdbEntityEvent, StdbPlugin,
+    StdbSet, UpdateEvent,
+    tables::{TableEvents, claim_table_channels, register_table, register_table_without_pk},
+};
+
+/// A row that can be mirrored as an entity, identified by its key.
+///
+/// ```ignore
+/// impl MirroredRow for Player {
+///     type Key = u64;
+///
+///     fn key(&self) -> u64 {
+///         self.id
+///     }
+/// }
+/// ```
+pub trait MirroredRow: Send + Sync + Clone + 'static {
+    /// The key of the row, usually its primary key.
+    type Key: Eq + Hash + Clone + Send + Sync + 'static;
+
+    /// Returns the key of the row.
+    fn key(&self) -> Self::Key;
+}
+
+/// The row mirrored by an entity, see [`StdbPlugin::add_mirrored_table`].
+#[derive(Component, Debug, Clone, Deref, DerefMut)]
+pub struct StdbRow<TRow: MirroredRow>(pub TRow);
+
+/// The entities mirroring the rows of a table, by key.
+///
+/// The rows sharing a key, e.g. in a table without a primary key, are mirrored by the same entity,
+/// which is despawned once all of them are deleted.
+#[derive(Resource)]
+pub struct StdbMirror<TRow: MirroredRow> {
+    entities: HashMap<TRow::Key, Entity>,
+    /// The number of rows beyond the first one mirrored by the entity of a key.
+    duplicates: HashMap<TRow::Key, usize>,
+}
+
+impl<TRow: MirroredRow> Default for StdbMirror<TRow> {
+    fn default() -> Self {
+        Self {
+            entities: HashMap::default(),
+            duplicates: HashMap::default(),
+        }
+    }
+}
+
+impl<TRow: MirroredRow> StdbMirror<TRow> {
+    /// The entity mirroring the row with `key`, if any.
+    pub fn entity(&self, key: &TRow::Key) -> Option<Entity> {
+        self.entities.get(key).copied()
+    }
+
+    /// The keys of the mirrored rows with their entity.
+    pub fn iter(&self) -> impl Iterator<Item = (&TRow::Key, Entity)> {
+        self.entities.iter().map(|(key, entity)| (key, *entity))
+    }
+
+    /// The number of mirrored keys, i.e. of entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Whether no row is mirrored.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Counts another row mirrored by the entity of `key`, if there is one.
+    fn add_duplicate(&mut self, key: &TRow::Key) -> Option<Entity> {
+        let entity = self.entity(key)?;
+        *self.duplicates.entry(key.clone()).or_default() += 1;
+        Some(entity)
+    }
+
+    /// Removes a row with `key`, returning its entity and whether it was the last row mirrored by it.
+    fn remove_row(&mut self, key: &TRow::Key) -> Option<(Entity, bool)> {
+        if let Some(duplicates) = self.duplicates.get_mut(key) {
+            *duplicates -= 1;
+            if *duplicates == 0 {
+                self.duplicates.remove(key);
+            }
+            return self.entity(key).map(|entity| (entity, false));
+        }
+        self.entities.remove(key).map(|entity| (entity, true))
+    }
+}
+
+/// Maps the rows of a table to the components of their entity, registered with [`StdbPlugin::add_mapped_table`].
+///
+/// ```ignore
+/// impl StdbRowMapper for Player {
+///     type Row = Player;
+///
+///     fn spawn(row: &Player, entity: &mut EntityCommands) {
+///         entity.insert((PlayerId(row.id), Name::new(row.name.clone()), Transform::default()));
+///     }
+///
+///     fn update(_old: &Player, new: &Player, entity: &mut EntityCommands) {
+///         entity.insert(Name::new(new.name.clone()));
+///     }
+/// }
+/// ```
+pub trait StdbRowMapper: Send + Sync + 'static {
+    /// The row mapped to the entity.
+    type Row: MirroredRow;
+
+    /// Inserts the components of the entity of a row when it is inserted.
+    fn spawn(row: &Self::Row, entity: &mut EntityCommands);
+
+    /// Updates the components of the entity of a row when it is updated.
+    fn update(old: &Self::Row, new: &Self::Row, entity: &mut EntityCommands);
+
+    /// Called when the row is deleted, despawns the entity by default.
+    fn despawn(_row: &Self::Row, entity: &mut EntityCommands) {
+        entity.try_despawn();
+    }
+}
+
+/// The mapper of [`StdbPlugin::add_mirrored_table`], mirroring the whole row in a [`StdbRow`] component.
+pub(crate) struct RowComponentMapper<TRow>(PhantomData<TRow>);
+
+impl<TRow: MirroredRow> StdbRowMapper for RowComponentMapper<TRow> {
+    type Row = TRow;
+
+    fn spawn(row: &TRow, entity: &mut EntityCommands) {
+        entity.try_insert(StdbRow(row.clone()));
+    }
+
+    fn update(_old: &TRow, new: &TRow, entity: &mut EntityCommands) {
+        entity.try_insert(StdbRow(new.clone()));
+    }
+}
+
+impl<
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext + Sync,
+    M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
+> StdbPlugin<C, M>
+{
+    /// Registers a table like [`Self::add_table`], and mirrors each of its rows as an entity
+    /// with a [`StdbRow`] component, found by key in the [`StdbMirror`] resource.
+    ///
+    /// The entities are spawned, updated and despawned in [`StdbSet::ApplyMirror`], in the order of the
+    /// [`RowChangeEvent`]s. With [`Self::with_triggered_events`], the table events are also triggered on them
+    /// as [`StdbEntityEvent`]s. Its table events are registered as well, registering the table again with
+    /// [`Self::add_table`] makes the plugin fail with [`crate::StdbPluginError::DuplicateEventChannel`].
+    pub fn add_mirrored_table<TRow, TTable, F>(self, accessor: F) -> Self
+    where
+        TRow: MirroredRow,
+        TTable: Table<
+                Row = TRow,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + TableWithPrimaryKey<UpdateCallbackId: Send + Sync + 'static>
+            + 'static,
+        F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
+    {
+        self.add_mapped_table::<RowComponentMapper<TRow>, TTable, F>(accessor)
+    }
+
+    /// Registers a table without a primary key like [`Self::add_table_without_pk`], and mirrors each of its rows
+    /// as an entity like [`Self::add_mirrored_table`].
+    ///
+    /// The rows with the same key share an entity, with the components of the last one inserted,
+    /// which is despawned once all of them are deleted.
+    pub fn add_mirrored_table_without_pk<TRow, TTable, F>(self, accessor: F) -> Self
+    where
+        TRow: MirroredRow,
+        TTable: Table<
+                Row = TRow,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + 'static,
+        F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
+    {
+        self.add_mapped_table_without_pk::<RowComponentMapper<TRow>, TTable, F>(accessor)
+    }
+
+    /// Registers a table like [`Self::add_mirrored_table`], mapping each of its rows to the components
+    /// of an entity with the mapper <R>.
+    pub fn add_mapped_table<R, TTable, F>(mut self, accessor: F) -> Self
+    where
+        R: StdbRowMapper,
+        TTable: Table<
+                Row = R::Row,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + TableWithPrimaryKey<UpdateCallbackId: Send + Sync + 'static>
+            + 'static,
+        F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
+    {
+        claim_table_channels::<R::Row>(&mut self.channel_claims, TableEvents::all(), true);
+        let accessor = Arc::new(accessor);
+        let register = move |app: &mut App, conn: &'static C| {
+            register_table(app, conn, accessor.clone(), TableEvents::all(), true)?;
+            register_mirror::<R>(app);
+            Ok(())
+        };
+        self.table_registers.push(Box::new(register));
+
+        self
+    }
+
+    /// Registers a table without a primary key like [`Self::add_mirrored_table_without_pk`], mapping each of its rows
+    /// to the components of an entity with the mapper <R>.
+    pub fn add_mapped_table_without_pk<R, TTable, F>(mut self, accessor: F) -> Self
+    where
+        R: StdbRowMapper,
+        TTable: Table<
+                Row = R::Row,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + 'static,
+        F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
+    {
+        claim_table_channels::<R::Row>(&mut self.channel_claims, TableEvents::no_update(), true);
+        let accessor = Arc::new(accessor);
+        let register = move |app: &mut App, conn: &'static C| {
+            register_table_without_pk(app, conn, accessor.clone(), TableEvents::no_update(), true)?;
+            register_mirror::<R>(app);
+            Ok(())
+        };
+        self.table_registers.push(Box::new(register));
+
+        self
+    }
+}
+
+/// The schedule in which the [`RowChangeEvent`]s of `TRow` are drained.
+pub(crate) fn mirror_schedule<TRow: MirroredRow>(app: &App) -> InternedScheduleLabel {
+    app.world().get_resource::<StdbChannelConfig>().map_or_else(
+        || PreUpdate.intern(),
+        |config| config.schedule_of::<RowChangeEvent<TRow>>(),
+    )
+}
+
+/// The steps of [`StdbSet::ApplyMirror`].
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum MirrorSet {
+    /// The entities about to be despawned are detached from their children.
+    Detach,
+    /// The row changes are applied to the entities.
+    Rows,
+    /// The entities are attached to their parent.
+    Attach,
+}
+
+/// Adds the [`StdbMirror`] of the rows of <R> and the system applying their [`RowChangeEvent`]s.
+pub(crate) fn register_mirror<R: StdbRowMapper>(app: &mut App) {
+    let schedule = mirror_schedule::<R::Row>(app);
+    app.init_resource::<StdbMirror<R::Row>>()
+        .configure_sets(
+            schedule,
+            (MirrorSet::Detach, MirrorSet::Rows, MirrorSet::Attach)
+                .chain()
+                .in_set(StdbSet::ApplyMirror),
+        )
+        .add_systems(schedule, apply_row_changes::<R>.in_set(MirrorSet::Rows));
+}
+
+/// Spawns, updates and despawns the entities mapped from the rows of <R>,
+/// triggering the table events on them if enabled in the [`StdbChannelConfig`].
+pub(crate) fn apply_row_changes<R: StdbRowMapper>(
+    mut changes: MessageReader<RowChangeEvent<R::Row>>,
+    mut mirror: ResMut<StdbMirror<R::Row>>,
+    config: Option<Res<StdbChannelConfig>>,
+    mut commands: Commands,
+) {
+    // Triggered after the commands of the mapper, so observers see the components of the entity
+    let trigger = config.is_some_and(|config| config.trigger_events);
+
+    for change in changes.read() {
+        match change {
+            RowChangeEvent::Insert(row) => {
+                let entity = match mirror.add_duplicate(&row.key()) {
+                    // Another row with the same key, e.g. in a table without a primary key
+                    Some(entity) => {
+                        R::spawn(row, &mut commands.entity(entity));
+                        entity
+                    }
+                    None => {
+                        let mut entity = commands.spawn_empty();
+                        R::spawn(row, &mut entity);
+                        mirror.entities.insert(row.key(), entity.id());
+                        entity.id()
+                    }
+                };
+                if trigger {
+                    RowEvent::Insert(row).trigger(&mut commands, entity);
+                }
+            }
+            RowChangeEvent::Update { old, new } => {
+                let (old_key, new_key) = (old.key(), new.key());
+                let entity = if old_key == new_key
+                    && let Some(entity) = mirror.entity(&old_key)
+                {
+                    R::update(old, new, &mut commands.entity(entity));
+                    entity
+                } else {
+                    let entity = match mirror.remove_row(&old_key) {
+                        // The entity follows its row to its new key
+                        Some((entity, true)) => {
+                            R::update(old, new, &mut commands.entity(entity));
+                            entity
+                        }
+                        // The entity of the old key still mirrors the other rows with that key
+                        Some((_, false)) | None => {
+                            let mut entity = commands.spawn_empty();
+                            R::spawn(new, &mut entity);
+                            entity.id()
+                        }
+                    };
+                    mirror.entities.insert(new_key, entity);
+                    entity
+                };
+                if trigger {
+                    RowEvent::Update(old, new).trigger(&mut commands, entity);
+                }
+            }
+            RowChangeEvent::Delete(row) => {
+                if let Some((entity, last)) = mirror.remove_row(&row.key()) {
+                    // Before the entity is despawned
+                    if trigger {
+                        RowEvent::Delete(row).trigger(&mut commands, entity);
+                    }
+                    // Otherwise it still mirrors the other rows with the same key
+                    if last {
+                        R::despawn(row, &mut commands.entity(entity));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A table event to trigger on the entity of its row.
+enum RowEvent<'a, TRow> {
+    Insert(&'a TRow),
+    Update(&'a TRow, &'a TRow),
+    Delete(&'a TRow),
+}
+
+impl<TRow: Send + Sync + Clone + 'static> RowEvent<'_, TRow> {
+    fn trigger(self, commands: &mut Commands, entity: Entity) {
+        match self {
+            Self::Insert(row) => commands.trigger(StdbEntityEvent {
+                entity,
+                event: InsertEvent { row: row.clone() },
+            }),
+            Self::Update(old, new) => commands.trigger(StdbEntityEvent {
+                entity,
+                event: UpdateEvent {
+                    old: old.clone(),
+                    new: new.clone(),
+                },
+            }),
+            Self::Delete(row) => commands.trigger(StdbEntityEvent {
+                entity,
+                event: DeleteEvent { row: row.clone() },
+            }),
+        }
+    }
+}
+
+/// Returns the key of the mirrored row of `TRow` targeted by a message `T`, if any.
+pub(crate) type TargetKey<T, TRow> =
+    Arc<dyn Fn(&T) -> Option<<TRow as MirroredRow>::Key> + Send + Sync>;
+
+/// The rows targeted by the messages `T`, see [`trigger_on_mirrored_entities`].
+#[derive(Resource)]
+struct EntityTargets<T, TRow: MirroredRow> {
+    key: TargetKey<T, TRow>,
+}
+
+/// Also triggers the `T` messages as [`StdbEntityEvent`]s on the entity mirroring the row of `TRow`
+/// returned by `key`, in [`StdbSet::Dispatch`].
+pub(crate) fn trigger_on_mirrored_entities<T, TRow>(app: &mut App, key: TargetKey<T, TRow>)
+where
+    T: Message + Clone,
+    TRow: MirroredRow,
+{
+    let schedule = app
+        .world()
+        .get_resource::<StdbChannelConfig>()
+        .map_or_else(|| PreUpdate.intern(), |config| config.schedule_of::<T>());
+    app.insert_resource(EntityTargets::<T, TRow> { key })
+        .add_systems(
+            schedule,
+            trigger_entity_events::<T, TRow>.in_set(StdbSet::Dispatch),
+        );
+}
+
+fn trigger_entity_events<T, TRow>(
+    mut messages: MessageReader<T>,
+    targets: Res<EntityTargets<T, TRow>>,
+    mirror: Option<Res<StdbMirror<TRow>>>,
+    mut commands: Commands,
+) where
+    T: Message + Clone,
+    TRow: MirroredRow,
+{
+    let Some(mirror) = mirror else {
+        return;
+    };
+    for message in messages.read() {
+        if let Some(entity) = (targets.key)(message).and_then(|key| mirror.entity(&key)) {
+            commands.trigger(StdbEntityEvent {
+                entity,
+                event: message.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Player {
+        id: u32,
+        name: &'static str,
+    }
+
+    impl MirroredRow for Player {
+        type Key = u32;
+
+        fn key(&self) -> u32 {
+            self.id
+        }
+    }
+
+    /// The table events observed on the entities.
+    #[derive(Resource, Default)]
+    struct Observed(Vec<String>);
+
+    fn mirror_app() -> App {
+        let mut app = App::new();
+        app.insert_resource(StdbChannelConfig {
+            trigger_events: true,
+            ..Default::default()
+        })
+        .init_resource::<Observed>()
+        .add_message::<RowChangeEvent<Player>>();
+        register_mirror::<RowComponentMapper<Player>>(&mut app);
+        app
+    }
+
+    fn apply(app: &mut App, change: RowChangeEvent<Player>) {
+        app.world_mut().write_message(change);
+        app.update();
+    }
+
+    #[test]
+    fn table_events_are_triggered_on_the_mirrored_entity() {
+        let mut app = mirror_app();
+        let alice = Player {
+            id: 1,
+            name: "alice",
+        };
+        apply(&mut app, RowChangeEvent::Insert(alice.clone()));
+
+        let entity = app
+            .world()
+            .resource::<StdbMirror<Player>>()
+            .entity(&1)
+            .unwrap();
+        app.world_mut()
+            .entity_mut(entity)
+            .observe(
+                |event: On<StdbEntityEvent<UpdateEvent<Player>>>,
+                 mut observed: ResMut<Observed>| {
+                    observed.0.push(format!("update {}", event.event.new.name));
+                },
+            )
+            .observe(
+                |event: On<StdbEntityEvent<DeleteEvent<Player>>>,
+                 rows: Query<&StdbRow<Player>>,
+                 mut observed: ResMut<Observed>| {
+                    // Still alive with its components
+                    let row = rows.get(event.entity).unwrap();
+                    observed.0.push(format!("delete {}", row.name));
+                },
+            );
+
+        let renamed = Player { id: 1, name: "bob" };
+        apply(
+            &mut app,
+            RowChangeEvent::Update {
+                old: alice,
+                new: renamed.clone(),
+            },
+        );
+        apply(&mut app, RowChangeEvent::Delete(renamed));
+
+        assert_eq!(
+            app.world().resource::<Observed>().0,
+            ["update bob", "delete bob"]
+        );
+        assert!(app.world().get_entity(entity).is_err());
+    }
+
+    #[test]
+    fn rows_sharing_a_key_share_their_entity_until_all_are_deleted() {
+        let mut app = mirror_app();
+        let alice = Player {
+            id: 1,
+            name: "alice",
+        };
+        let bob = Player { id: 1, name: "bob" };
+        apply(&mut app, RowChangeEvent::Insert(alice.clone()));
+        apply(&mut app, RowChangeEvent::Insert(bob.clone()));
+
+        let mirror = app.world().resource::<StdbMirror<Player>>();
+        assert_eq!(mirror.len(), 1);
+        let entity = mirror.entity(&1).unwrap();
+
+        apply(&mut app, RowChangeEvent::Delete(alice));
+        assert_eq!(
+            app.world().resource::<StdbMirror<Player>>().entity(&1),
+            Some(entity)
+        );
+        assert!(app.world().get_entity(entity).is_ok());
+
+        apply(&mut app, RowChangeEvent::Delete(bob));
+        assert!(app.world().resource::<StdbMirror<Player>>().is_empty());
+        assert!(app.world().get_entity(entity).is_err());
+    }
+
+    #[test]
+    fn inserts_are_triggered_once_the_entity_is_spawned() {
+        let mut app = mirror_app();
+        app.add_observer(
+            |event: On<StdbEntityEvent<InsertEvent<Player>>>,
+             rows: Query<&StdbRow<Player>>,
+             mut observed: ResMut<Observed>| {
+                let row = rows.get(event.entity).unwrap();
+                observed.0.push(format!("insert {}", row.name));
+            },
+        );
+
+        apply(
+            &mut app,
+            RowChangeEvent::Insert(Player {
+                id: 1,
+                name: "alice",
+            }),
+        );
+        assert_eq!(app.world().resource::<Observed>().0, ["insert alice"]);
+    }
+}
@@ -1,17 +1,55 @@
 use crate::{
-    AddEventChannelAppExtensions, StdbConnectedEvent, StdbConnection, StdbConnectionErrorEvent,
-    StdbDisconnectedEvent,
+    AddEventChannelAppExtensions, BackpressurePolicy, DEFAULT_PROFILE, RunnableConnection,
+    StdbCallbackRegistry, StdbChannelConfig, StdbConfig, StdbConfigError, StdbConnectedEvent,
+    StdbConnection, StdbConnectionErrorEvent, StdbDisconnectedEvent, StdbEventBudget,
+    StdbTokenStore, TokenStore, channel_receiver::ChannelClaims, registry::PendingRegistrations,
+    runner::RunMode, subscriptions::register_subscriptions,
 };
 use bevy::{
     app::{App, Plugin},
-    platform::collections::HashMap,
+    ecs::{message::Message, schedule::ScheduleLabel},
+    log::warn,
 };
 use spacetimedb_sdk::{Compression, DbConnectionBuilder, DbContext};
-use std::{
-    any::{Any, TypeId},
-    sync::{Mutex, mpsc::channel},
-    thread::JoinHandle,
-};
+use std::{fmt, future::Future, sync::Arc, thread::JoinHandle};
+
+/// An error preventing the [`StdbPlugin`] from being built, returned by [`StdbPlugin::try_build`].
+#[derive(Debug)]
+pub enum StdbPluginError {
+    /// The plugin is misconfigured, all the problems found are listed.
+    InvalidConfig(Vec<StdbConfigError>),
+    /// An event channel was registered twice for the same message type, e.g. by adding a table or a reducer twice.
+    DuplicateEventChannel(&'static str),
+    /// The connection to SpacetimeDB couldn't be built.
+    Connection(spacetimedb_sdk::Error),
+}
+
+impl fmt::Display for StdbPluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidConfig(errors) => {
+                write!(f, "invalid StdbPlugin configuration:")?;
+                for err in errors {
+                    write!(f, "\n  - {err}")?;
+                }
+                Ok(())
+            }
+            Self::DuplicateEventChannel(type_name) => {
+                write!(f, "the event channel for {type_name} is already registered")
+            }
+            Self::Connection(err) => write!(f, "failed to build the connection: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StdbPluginError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Connection(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 /// The plugin for connecting SpacetimeDB with your bevy application.
 pub struct StdbPlugin<
@@ -21,19 +59,24 @@ pub struct StdbPlugin<
     module_name: Option<String>,
     uri: Option<String>,
     token: Option<String>,
-    run_fn: Option<fn(&C) -> JoinHandle<()>>,
+    run_mode: Option<RunMode<C>>,
     compression: Option<Compression>,
     light_mode: bool,
+    token_store: Option<Arc<dyn TokenStore>>,
+    profile: String,
+    channel_config: StdbChannelConfig,
+    event_budget: StdbEventBudget,
 
-    // Stores Senders for registered table events.
-    pub(crate) event_senders: Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
     #[allow(clippy::type_complexity)]
-    pub(crate) table_registers: Vec<
-        Box<dyn Fn(&StdbPlugin<C, M>, &mut App, &'static <C as DbContext>::DbView) + Send + Sync>,
-    >,
+    pub(crate) table_registers:
+        Vec<Box<dyn Fn(&mut App, &'static C) -> Result<(), StdbPluginError> + Send + Sync>>,
     #[allow(clippy::type_complexity)]
     pub(crate) reducer_registers:
-        Vec<Box<dyn Fn(&mut App, &<C as DbContext>::Reducers) + Send + Sync>>,
+        Vec<Box<dyn Fn(&mut App, &'static C) -> Result<(), StdbPluginError> + Send + Sync>>,
+    #[allow(clippy::type_complexity)]
+    pub(crate) callback_registers: Vec<Box<dyn Fn(&mut App, &'static C) + Send + Sync>>,
+    /// The event channels registered by the registers, checked before the plugin is built.
+    pub(crate) channel_claims: ChannelClaims,
 }
 
 impl<
@@ -46,13 +89,33 @@ impl<
             module_name: Default::default(),
             uri: None,
             token: None,
-            run_fn: None,
+            run_mode: None,
             compression: Some(Compression::default()),
             light_mode: false,
+            token_store: None,
+            profile: DEFAULT_PROFILE.to_string(),
+            channel_config: StdbChannelConfig::default(),
+            event_budget: StdbEventBudget::default(),
 
-            event_senders: Mutex::default(),
             table_registers: Vec::default(),
             reducer_registers: Vec::default(),
+            callback_registers: Vec::default(),
+            channel_claims: ChannelClaims::default(),
+        }
+    }
+}
+
+impl<
+    C: spacetimedb_sdk::__codegen::DbConnection<Module = M> + DbContext + RunnableConnection,
+    M: spacetimedb_sdk::__codegen::SpacetimeModule<DbConnection = C>,
+> StdbPlugin<C, M>
+{
+    /// Creates a plugin running the connection with [`RunnableConnection::run_threaded`],
+    /// [`StdbPlugin::with_run_fn`] can still be used to override it.
+    pub fn new() -> Self {
+        Self {
+            run_mode: Some(RunMode::Threaded(C::run_threaded)),
+            ..Default::default()
         }
     }
 }
@@ -65,8 +128,33 @@ impl<
     /// The function that the connection will run with. The recommended function is `DbConnection::run_threaded`.
     ///
     /// Other function are not tested, they may not work.
+    /// Optional when the plugin is created with [`StdbPlugin::new`].
     pub fn with_run_fn(mut self, run_fn: fn(&C) -> JoinHandle<()>) -> Self {
-        self.run_fn = Some(run_fn);
+        self.run_mode = Some(RunMode::Threaded(run_fn));
+        self
+    }
+
+    /// Advance the connection from a system in the drain schedule (`PreUpdate` by default)
+    /// instead of a background thread, with `DbConnection::frame_tick`.
+    ///
+    /// Messages are then processed at a deterministic point of the frame, before the event channels are drained,
+    /// and no thread is spawned to run the connection.
+    pub fn with_frame_tick(mut self, frame_tick: fn(&C) -> spacetimedb_sdk::Result<()>) -> Self {
+        self.run_mode = Some(RunMode::FrameTick(frame_tick));
+        self
+    }
+
+    /// Advance the connection from a task spawned on Bevy's `IoTaskPool` instead of a dedicated thread,
+    /// with `DbConnection::run_async` or any future built on `DbConnection::advance_one_message_async`.
+    ///
+    /// The task is available as the [`crate::StdbConnectionTask`] resource,
+    /// and is cancelled when the connection is lost or the app exits.
+    pub fn with_async_run_fn<F, Fut>(mut self, run_fn: F) -> Self
+    where
+        F: Fn(&'static C) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = spacetimedb_sdk::Result<()>> + Send + 'static,
+    {
+        self.run_mode = Some(RunMode::Async(Arc::new(move |conn| Box::pin(run_fn(conn)))));
         self
     }
 
@@ -121,6 +209,136 @@ impl<
         self.light_mode = light_mode;
         self
     }
+
+    /// Applies the settings set in `config`, e.g. loaded from environment variables with [`StdbConfig::from_env`].
+    ///
+    /// Settings which are not set in `config` are left unchanged.
+    pub fn with_config(mut self, config: StdbConfig) -> Self {
+        if config.uri.is_some() {
+            self.uri = config.uri;
+        }
+        if config.module_name.is_some() {
+            self.module_name = config.module_name;
+        }
+        if config.token.is_some() {
+            self.token = config.token;
+        }
+        if config.compression.is_some() {
+            self.compression = config.compression;
+        }
+        if let Some(light_mode) = config.light_mode {
+            self.light_mode = light_mode;
+        }
+        self
+    }
+
+    /// Bounds the number of pending messages per event channel, they are unbounded by default.
+    ///
+    /// Useful to avoid memory spikes when a large subscription is applied,
+    /// what happens when a channel is full is set with [`Self::with_backpressure`].
+    /// With [`Self::with_frame_tick`], the backpressure must drop messages instead of blocking.
+    pub fn with_channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_config.capacity = Some(capacity);
+        self
+    }
+
+    /// Sets what happens when a message is sent to a full event channel, see [`Self::with_channel_capacity`].
+    pub fn with_backpressure(mut self, backpressure: BackpressurePolicy) -> Self {
+        self.channel_config.backpressure = backpressure;
+        self
+    }
+
+    /// Sets the schedule in which the event channels are drained, `PreUpdate` by default.
+    ///
+    /// The [`crate::StdbSet`] system sets are configured in this schedule.
+    /// With [`Self::with_frame_tick`], the connection is advanced in this schedule as well.
+    pub fn with_drain_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.channel_config.schedule = schedule.intern();
+        self
+    }
+
+    /// Drains the event channel of message `T` in `schedule` instead of the one set with
+    /// [`Self::with_drain_schedule`], e.g. `InsertEvent<Player>` in `FixedPreUpdate` for a deterministic simulation.
+    pub fn with_message_schedule<T: Message>(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.channel_config
+            .message_schedules
+            .insert(std::any::TypeId::of::<T>(), schedule.intern());
+        self
+    }
+
+    /// Limits how many messages are drained from the event channels each frame, see [`StdbEventBudget`].
+    ///
+    /// The budget can be changed at runtime through the [`StdbEventBudget`] resource.
+    pub fn with_event_budget(mut self, budget: StdbEventBudget) -> Self {
+        self.event_budget = budget;
+        self
+    }
+
+    /// Also triggers the table events as Bevy events, so reactions can be written as observers:
+    ///
+    /// ```ignore
+    /// app.add_observer(|event: On<InsertEvent<Player>>| info!("Player inserted: {:?}", event.row));
+    /// ```
+    ///
+    /// Applies to all the tables, use [`Self::add_observed_reducer`] for reducers.
+    /// The events are still written as messages.
+    ///
+    /// The events of a mirrored table are also triggered on the entity of their row as [`crate::StdbEntityEvent`]s,
+    /// use [`Self::add_observed_reducer_on`] to target the entity of a row with a reducer event.
+    pub fn with_triggered_events(mut self, trigger_events: bool) -> Self {
+        self.channel_config.trigger_events = trigger_events;
+        self
+    }
+
+    /// Checks the configuration of the plugin, reporting all the problems at once.
+    pub fn validate(&self) -> Result<(), StdbPluginError> {
+        let mut errors = self.config().validate().err().unwrap_or_default();
+        if self.run_mode.is_none() {
+            errors.push(StdbConfigError::MissingRunFn);
+        }
+        if matches!(self.run_mode, Some(RunMode::FrameTick(_)))
+            && self.channel_config.capacity.is_some()
+            && self.channel_config.backpressure == BackpressurePolicy::Block
+        {
+            errors.push(StdbConfigError::BlockingFrameTick);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(StdbPluginError::InvalidConfig(errors))
+        }
+    }
+
+    /// The connection settings of the plugin.
+    pub fn config(&self) -> StdbConfig {
+        StdbConfig {
+            uri: self.uri.clone(),
+            module_name: self.module_name.clone(),
+            token: self.token.clone(),
+            compression: self.compression,
+            light_mode: Some(self.light_mode),
+        }
+    }
+
+    /// Persists the access token in `store`, so the same `Identity` is reused between runs.
+    ///
+    /// The saved token is loaded before connecting, unless one is supplied with [`Self::with_token`],
+    /// and the token received on connection is saved afterwards.
+    /// The store is then available as the [`StdbTokenStore`] resource, to forget the identity.
+    pub fn with_token_store(mut self, store: impl TokenStore) -> Self {
+        self.token_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Set the profile under which the token is saved in the token store. Defaults to `"default"`.
+    ///
+    /// Use distinct profiles to keep several identities, e.g. one per local player. The [`crate::FileTokenStore`]
+    /// only accepts profiles made of ASCII letters, digits, `-` and `_`.
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = profile.into();
+        self
+    }
 }
 
 impl<
@@ -129,63 +347,123 @@ impl<
 > Plugin for StdbPlugin<C, M>
 {
     fn build(&self, app: &mut App) {
-        self.uri
+        if let Err(err) = self.try_build(app) {
+            panic!("{err}");
+        }
+    }
+}
+
+impl<
+    C: spacetimedb_sdk::__codegen::DbConnection<Module = M> + DbContext + Sync,
+    M: spacetimedb_sdk::__codegen::SpacetimeModule<DbConnection = C>,
+> StdbPlugin<C, M>
+{
+    /// Builds the plugin like [`Plugin::build`], but returns an error instead of panicking
+    /// when the plugin is misconfigured or the connection can't be built.
+    ///
+    /// Use it instead of adding the plugin with `App::add_plugins`.
+    /// The app is left unchanged when an error is returned.
+    pub fn try_build(&self, app: &mut App) -> Result<(), StdbPluginError> {
+        self.validate()?;
+        self.check_event_channels(app)?;
+        let config = self.config();
+
+        // Registered once the connection is built
+        let (send_connect_error, connect_error_receiver) = self.channel_config.channel();
+        let (send_connected, connected_receiver) = self.channel_config.channel();
+        let (send_disconnected, disconnected_receiver) = self.channel_config.channel();
+
+        let token_store = self
+            .token_store
             .clone()
-            .expect("No uri set for StdbPlugin. Set it with the with_uri() function");
-        self.module_name.clone().expect(
-            "No module name set for StdbPlugin. Set it with the with_module_name() function",
-        );
-
-        let (send_connected, recv_connected) = channel::<StdbConnectedEvent>();
-        let (send_disconnected, recv_disconnected) = channel::<StdbDisconnectedEvent>();
-        let (send_connect_error, recv_connect_error) = channel::<StdbConnectionErrorEvent>();
-        app.add_event_channel::<StdbConnectionErrorEvent>(recv_connect_error)
-            .add_event_channel::<StdbConnectedEvent>(recv_connected)
-            .add_event_channel::<StdbDisconnectedEvent>(recv_disconnected);
-
-        // FIXME App should not crash if intial connection fails.
+            .map(|store| StdbTokenStore::new(store, self.profile.clone()));
+        let token = self.token.clone().or_else(|| {
+            let store = token_store.as_ref()?;
+            store
+                .load()
+                .inspect_err(|err| warn!("Failed to load SpacetimeDB token: {err}"))
+                .ok()
+                .flatten()
+        });
+        let save_token = token_store.clone();
+
         let conn = DbConnectionBuilder::<M>::new()
-            .with_module_name(self.module_name.clone().unwrap())
-            .with_uri(self.uri.clone().unwrap())
-            .with_token(self.token.clone())
+            .with_module_name(config.module_name.unwrap())
+            .with_uri(config.uri.unwrap())
+            .with_token(token)
             .with_compression(self.compression.unwrap_or_default())
             .with_light_mode(self.light_mode)
             .on_connect_error(move |_ctx, err| {
-                send_connect_error
-                    .send(StdbConnectionErrorEvent { err })
-                    .unwrap();
+                send_connect_error.send(StdbConnectionErrorEvent { err });
             })
             .on_disconnect(move |_ctx, err| {
-                send_disconnected
-                    .send(StdbDisconnectedEvent { err })
-                    .unwrap();
+                send_disconnected.send(StdbDisconnectedEvent { err });
             })
             .on_connect(move |_ctx, id, token| {
-                send_connected
-                    .send(StdbConnectedEvent {
-                        identity: id,
-                        access_token: token.to_string(),
-                    })
-                    .unwrap();
+                if let Some(store) = save_token
+                    && let Err(err) = store.save(token)
+                {
+                    warn!("Failed to save SpacetimeDB token: {err}");
+                }
+                send_connected.send(StdbConnectedEvent {
+                    identity: id,
+                    access_token: token.to_string(),
+                });
             })
             .build()
-            .expect("Failed to build connection");
+            .map_err(StdbPluginError::Connection)?;
+
+        app.insert_resource(self.channel_config.clone())
+            .insert_resource(self.event_budget);
+        app.try_add_event_channel::<StdbConnectionErrorEvent>(connect_error_receiver)?
+            .try_add_event_channel::<StdbConnectedEvent>(connected_receiver)?
+            .try_add_event_channel::<StdbDisconnectedEvent>(disconnected_receiver)?;
+        register_subscriptions(app);
 
         // A 'static ref is needed for the connection the register tables and reducers
         // This is fine because only a small and fixed amount of memory will be leaked
         // Because conn has to live until the end of the program anyways, not using it would not make for any performance improvements.
         let conn = Box::<C>::leak(Box::new(conn));
 
+        app.init_resource::<StdbCallbackRegistry>();
         for table_register in self.table_registers.iter() {
-            table_register(self, app, conn.db());
+            table_register(app, conn)?;
         }
         for reducer_register in self.reducer_registers.iter() {
-            reducer_register(app, conn.reducers());
+            reducer_register(app, conn)?;
+        }
+        for callback_register in self.callback_registers.iter() {
+            callback_register(app, conn);
+        }
+        // Registered with the StdbAppExtensions before the plugin was built
+        if let Some(pending) = app.world_mut().remove_resource::<PendingRegistrations<C>>() {
+            for register in pending.registers {
+                register(app, conn)?;
+            }
         }
-
-        let run_fn = self.run_fn.expect("No run function specified!");
-        run_fn(conn);
 
         app.insert_resource(StdbConnection::new(conn));
+
+        let run_mode = self.run_mode.clone().expect("run mode checked by validate");
+        run_mode.run(app, conn, self.channel_config.schedule);
+        if let Some(token_store) = token_store {
+            app.insert_resource(token_store);
+        }
+
+        Ok(())
+    }
+
+    /// Returns an error if an event channel of the plugin is registered twice,
+    /// before anything is added to the app.
+    fn check_event_channels(&self, app: &App) -> Result<(), StdbPluginError> {
+        let mut claims = ChannelClaims::default();
+        claims.exclusive::<StdbConnectionErrorEvent>();
+        claims.exclusive::<StdbConnectedEvent>();
+        claims.exclusive::<StdbDisconnectedEvent>();
+        claims.extend(&self.channel_claims);
+        if let Some(pending) = app.world().get_resource::<PendingRegistrations<C>>() {
+            claims.extend(&pending.claims);
+        }
+        claims.check(app)
     }
 }
@@ -0,0 +1,273 @@
+use std::any::TypeId;
+
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use spacetimedb_sdk::{__codegen as spacetime_codegen, Status};
+
+use crate::{
+    ReducerResultEvent, RegisterableReducerEvent, RowChangeEvent, StdbChannelConfig,
+    StdbConnection, StdbPlugin, StdbSet,
+    mirror::{MirrorSet, MirroredRow, StdbMirror, StdbRow, mirror_schedule},
+};
+
+/// A local change of a mirrored row, waiting for the result of the reducer call it predicts.
+struct Prediction<TRow> {
+    /// The reducer event type of the call.
+    reducer: TypeId,
+    entity: Entity,
+    mutate: Box<dyn Fn(&mut TRow) + Send + Sync>,
+}
+
+/// The predictions pending on the mirrored rows `TRow`, see [`StdbPredictionExtensions::predict_stdb`].
+#[derive(Resource)]
+pub struct StdbPredictions<TRow: MirroredRow> {
+    /// The pending predictions, in the order of the reducer calls.
+    pending: Vec<Prediction<TRow>>,
+    /// The last authoritative row of the entities with pending predictions.
+    confirmed: HashMap<Entity, TRow>,
+    /// The entities whose row must be computed again from the authoritative one.
+    dirty: HashSet<Entity>,
+}
+
+impl<TRow: MirroredRow> Default for StdbPredictions<TRow> {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            confirmed: HashMap::default(),
+            dirty: HashSet::default(),
+        }
+    }
+}
+
+impl<TRow: MirroredRow> StdbPredictions<TRow> {
+    /// Whether `entity` has predictions waiting for their reducer result.
+    pub fn is_predicted(&self, entity: Entity) -> bool {
+        self.confirmed.contains_key(&entity)
+    }
+
+    /// The last authoritative row of `entity`, if it has pending predictions.
+    pub fn confirmed(&self, entity: Entity) -> Option<&TRow> {
+        self.confirmed.get(&entity)
+    }
+
+    /// The number of pending predictions.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether no prediction is pending.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Allows predicting the changes of reducer calls on mirrored rows.
+pub trait StdbPredictionExtensions {
+    /// Applies `mutate` to the [`StdbRow<TRow>`] of `entity` right away, predicting the reducer call <E>
+    /// made by this client.
+    ///
+    /// Until the [`ReducerResultEvent<E>`] of the call is received, `mutate` is applied again on top of
+    /// each authoritative update of the row. The prediction is then dropped: it is rolled back if the reducer
+    /// failed, and replaced by the authoritative row otherwise.
+    ///
+    /// The reducer must be registered with [`StdbPlugin::add_predicted_reducer`], and the entity must have
+    /// a [`StdbRow<TRow>`] component, e.g. mirrored with [`StdbPlugin::add_mirrored_table`].
+    fn predict_stdb<E, TRow>(
+        &mut self,
+        entity: Entity,
+        mutate: impl Fn(&mut TRow) + Send + Sync + 'static,
+    ) -> &mut Self
+    where
+        E: Send + Sync + 'static,
+        TRow: MirroredRow;
+}
+
+impl StdbPredictionExtensions for Commands<'_, '_> {
+    fn predict_stdb<E, TRow>(
+        &mut self,
+        entity: Entity,
+        mutate: impl Fn(&mut TRow) + Send + Sync + 'static,
+    ) -> &mut Self
+    where
+        E: Send + Sync + 'static,
+        TRow: MirroredRow,
+    {
+        self.queue(move |world: &mut World| {
+            let Some(mut row) = world.get_mut::<StdbRow<TRow>>(entity) else {
+                warn!(
+                    "Cannot predict {} on {entity}, which does not mirror a {} row",
+                    ShortName::of::<E>(),
+                    ShortName::of::<TRow>()
+                );
+                return;
+            };
+            let confirmed = row.0.clone();
+            mutate(&mut row.0);
+
+            let mut predictions = world.get_resource_or_init::<StdbPredictions<TRow>>();
+            predictions.confirmed.entry(entity).or_insert(confirmed);
+            predictions.pending.push(Prediction {
+                reducer: TypeId::of::<E>(),
+                entity,
+                mutate: Box::new(mutate),
+            });
+        });
+        self
+    }
+}
+
+impl<
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext + Sync,
+    M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
+> StdbPlugin<C, M>
+{
+    /// Registers a reducer event <E> like [`Self::add_reducer`], whose calls can be predicted on the rows
+    /// of the table mirrored with [`Self::add_mirrored_table`], see [`StdbPredictionExtensions::predict_stdb`].
+    ///
+    /// The predictions are applied to the [`StdbRow<TRow>`] component, so a table registered with
+    /// [`Self::add_mapped_table`] can only be predicted if its mapper inserts it.
+    pub fn add_predicted_reducer<E, TRow>(mut self) -> Self
+    where
+        E: RegisterableReducerEvent<C, M> + Send + Sync + 'static,
+        TRow: MirroredRow,
+    {
+        self = self.add_reducer::<E>();
+        self.reducer_registers
+            .push(Box::new(|app: &mut App, _: &'static C| {
+                // Predictions are applied along with the mirror of the rows
+                let schedule = mirror_schedule::<TRow>(app);
+                if !app.world().contains_resource::<StdbPredictions<TRow>>() {
+                    app.init_resource::<StdbPredictions<TRow>>().add_systems(
+                        schedule,
+                        reconcile_predictions::<TRow>
+                            .after(MirrorSet::Rows)
+                            .in_set(StdbSet::ApplyMirror),
+                    );
+                }
+
+                // The results are read where they are drained, which may be another schedule than the rows
+                let result_schedule = app
+                    .world()
+                    .get_resource::<StdbChannelConfig>()
+                    .map_or(schedule, |config| {
+                        config.schedule_of::<ReducerResultEvent<E>>()
+                    });
+                let resolve = resolve_predictions::<C, M, E, TRow>.in_set(StdbSet::ApplyMirror);
+                if result_schedule == schedule {
+                    app.add_systems(
+                        schedule,
+                        resolve
+                            .after(MirrorSet::Rows)
+                            .before(reconcile_predictions::<TRow>),
+                    );
+                } else {
+                    app.add_systems(result_schedule, resolve);
+                }
+                Ok(())
+            }));
+
+        self
+    }
+}
+
+/// Drops the oldest prediction of <E> for each result of a call made by this client.
+fn resolve_predictions<C, M, E, TRow>(
+    mut events: MessageReader<ReducerResultEvent<E>>,
+    mut predictions: ResMut<StdbPredictions<TRow>>,
+    stdb: Res<StdbConnection<C>>,
+) where
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext + Sync,
+    M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
+    E: RegisterableReducerEvent<C, M> + Send + Sync + 'static,
+    TRow: MirroredRow,
+{
+    // The calls of this client can't be recognized until it is connected
+    let Some(connection_id) = stdb.try_connection_id() else {
+        events.clear();
+        return;
+    };
+    for event in events.read() {
+        let Some(reducer_event) = event.result.reducer_event() else {
+            continue;
+        };
+        if reducer_event.caller_connection_id != Some(connection_id) {
+            continue;
+        }
+        let Some(index) = predictions
+            .pending
+            .iter()
+            .position(|prediction| prediction.reducer == TypeId::of::<E>())
+        else {
+            continue;
+        };
+
+        let prediction = predictions.pending.remove(index);
+        if !matches!(reducer_event.status, Status::Committed) {
+            debug!(
+                "Rolling back the prediction of {} on {}: {:?}",
+                ShortName::of::<E>(),
+                prediction.entity,
+                reducer_event.status
+            );
+        }
+        predictions.dirty.insert(prediction.entity);
+    }
+}
+
+/// Applies the pending predictions on top of the authoritative rows received, and rolls back the rows
+/// whose predictions were dropped.
+fn reconcile_predictions<TRow: MirroredRow>(
+    mut changes: MessageReader<RowChangeEvent<TRow>>,
+    mut predictions: ResMut<StdbPredictions<TRow>>,
+    mirror: Res<StdbMirror<TRow>>,
+    mut rows: Query<&mut StdbRow<TRow>>,
+) {
+    for change in changes.read() {
+        let row = match change {
+            RowChangeEvent::Insert(row) | RowChangeEvent::Update { new: row, .. } => row,
+            RowChangeEvent::Delete(_) => continue,
+        };
+        let Some(entity) = mirror.entity(&row.key()) else {
+            continue;
+        };
+        if let Some(confirmed) = predictions.confirmed.get_mut(&entity) {
+            *confirmed = row.clone();
+            predictions.dirty.insert(entity);
+        }
+    }
+
+    if predictions.dirty.is_empty() {
+        return;
+    }
+    let predictions = predictions.into_inner();
+    for entity in predictions.dirty.drain() {
+        let Some(confirmed) = predictions.confirmed.get(&entity) else {
+            continue;
+        };
+        let Ok(mut row) = rows.get_mut(entity) else {
+            // The row was deleted
+            predictions.confirmed.remove(&entity);
+            predictions
+                .pending
+                .retain(|prediction| prediction.entity != entity);
+            continue;
+        };
+
+        let mut predicted = confirmed.clone();
+        let mut is_predicted = false;
+        for prediction in predictions
+            .pending
+            .iter()
+            .filter(|prediction| prediction.entity == entity)
+        {
+            (prediction.mutate)(&mut predicted);
+            is_predicted = true;
+        }
+        row.0 = predicted;
+        if !is_predicted {
+            predictions.confirmed.remove(&entity);
+        }
+    }
+}
@@ -1,7 +1,17 @@
-use crate::{AddEventChannelAppExtensions, ReducerResultEvent, StdbPlugin};
-use bevy::app::App;
-use spacetimedb_sdk::__codegen as spacetime_codegen;
-use std::sync::mpsc::{Sender, channel};
+use std::sync::Arc;
+
+use crate::mirror::{MirroredRow, TargetKey, trigger_on_mirrored_entities};
+use crate::registry::RemoveCallback;
+use crate::{
+    AddEventChannelAppExtensions, AnyReducerEvent, ReducerResultEvent, StdbCallbackRegistry,
+    StdbChannelConfig, StdbPlugin, StdbSender, diagnostics::add_reducer_diagnostics,
+};
+use bevy::{
+    app::{App, PreUpdate},
+    ecs::schedule::ScheduleLabel,
+    log::{info_span, tracing::Span},
+};
+use spacetimedb_sdk::{__codegen as spacetime_codegen, ReducerEvent};
 
 /// Trait for making a reducer registerable into the bevy application.
 pub trait RegisterableReducerEvent<
@@ -10,12 +20,52 @@ pub trait RegisterableReducerEvent<
 > where
     Self: Sized,
 {
+    /// The id of the callback registered by [`Self::set_stdb_callback`], e.g. `SetNameCallbackId`.
+    type CallbackId: Send + Sync + 'static;
+
     /// The function that should the stdb callback behaviour, and send a bevy event through sender.
-    fn set_stdb_callback(reducers: &C::Reducers, sender: Sender<ReducerResultEvent<Self>>);
+    fn set_stdb_callback(
+        reducers: &C::Reducers,
+        sender: StdbSender<ReducerResultEvent<Self>>,
+    ) -> Self::CallbackId;
+
+    /// Removes the callback registered by [`Self::set_stdb_callback`].
+    fn remove_stdb_callback(reducers: &C::Reducers, id: Self::CallbackId);
+
+    /// The event of the reducer invocation, telling its status and caller.
+    fn reducer_event(&self) -> Option<&ReducerEvent<M::Reducer>> {
+        None
+    }
+}
+
+/// The connection a reducer event is registered on, implemented by `#[derive(RegisterReducerEvent)]`.
+///
+/// Allows registering the event without naming the connection, see [`crate::StdbAppExtensions::add_stdb_reducer`].
+pub trait ReducerEventConnection {
+    /// The generated `DbConnection`.
+    type Connection: spacetime_codegen::DbConnection<Module = Self::Module>
+        + spacetimedb_sdk::DbContext
+        + Sync;
+    /// The generated `RemoteModule`.
+    type Module: spacetime_codegen::SpacetimeModule<DbConnection = Self::Connection>;
+}
+
+/// Registers a callback on every reducer of a module, implemented for the generated `RemoteReducers`
+/// by the [`stdb_reducers!`](crate::stdb_reducers) macro.
+pub trait AnyReducerCallbacks: Sync + 'static {
+    /// The module's `Reducer` enum.
+    type Reducer: Send + Sync + Clone + 'static;
+
+    /// Registers a callback on every reducer, sending an [`AnyReducerEvent`] through `sender`,
+    /// and returns the functions removing them.
+    fn on_any_reducer(
+        &'static self,
+        sender: StdbSender<AnyReducerEvent<Self::Reducer>>,
+    ) -> Vec<Box<dyn FnOnce() + Send + Sync>>;
 }
 
 impl<
-    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext,
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext + Sync,
     M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
 > StdbPlugin<C, M>
 {
@@ -23,11 +73,12 @@ impl<
     pub fn add_reducer<E: RegisterableReducerEvent<C, M> + Send + Sync + 'static>(
         mut self,
     ) -> Self {
+        self.channel_claims.exclusive::<ReducerResultEvent<E>>();
         // This callback manages the registration of the event.
-        let register_fn = move |app: &mut App, reducers: &C::Reducers| {
-            let (send, recv) = channel::<ReducerResultEvent<E>>();
-            app.add_event_channel(recv);
-            E::set_stdb_callback(reducers, send);
+        let register_fn = move |app: &mut App, conn: &'static C| {
+            let send = app.try_add_event_sender::<ReducerResultEvent<E>>()?;
+            register_reducer::<C, M, E>(app, conn, send);
+            Ok(())
         };
 
         // The register_fn will get called once the connection is built.
@@ -35,4 +86,117 @@ impl<
 
         self
     }
+
+    /// Registers a reducer event <E> like [`Self::add_reducer`], also triggering its
+    /// [`ReducerResultEvent`] as a Bevy event so reactions can be written as observers.
+    pub fn add_observed_reducer<
+        E: RegisterableReducerEvent<C, M> + Clone + Send + Sync + 'static,
+    >(
+        mut self,
+    ) -> Self {
+        self = self.add_reducer::<E>();
+        self.reducer_registers
+            .push(Box::new(|app: &mut App, _: &'static C| {
+                app.trigger_stdb_message::<ReducerResultEvent<E>>();
+                Ok(())
+            }));
+
+        self
+    }
+
+    /// Registers a reducer event <E> like [`Self::add_observed_reducer`], also triggering its
+    /// [`ReducerResultEvent`] as a [`crate::StdbEntityEvent`] on the entity mirroring the row of `TRow`
+    /// whose key is returned by `key`, e.g. the player it was called for:
+    ///
+    /// ```ignore
+    /// StdbPlugin::default()
+    ///     .add_mirrored_table(RemoteTables::players)
+    ///     .add_observed_reducer_on::<SetName, Player>(|event| Some(event.player_id))
+    /// ```
+    ///
+    /// The table of `TRow` must be mirrored, e.g. with [`Self::add_mirrored_table`].
+    pub fn add_observed_reducer_on<E, TRow>(
+        mut self,
+        key: impl Fn(&E) -> Option<TRow::Key> + Send + Sync + 'static,
+    ) -> Self
+    where
+        E: RegisterableReducerEvent<C, M> + Clone + Send + Sync + 'static,
+        TRow: MirroredRow,
+    {
+        self = self.add_observed_reducer::<E>();
+        let key: TargetKey<ReducerResultEvent<E>, TRow> =
+            Arc::new(move |event: &ReducerResultEvent<E>| key(&event.result));
+        self.reducer_registers
+            .push(Box::new(move |app: &mut App, _: &'static C| {
+                trigger_on_mirrored_entities::<_, TRow>(app, key.clone());
+                Ok(())
+            }));
+
+        self
+    }
+
+    /// Registers the [`AnyReducerEvent`], written for every reducer invocation observed.
+    ///
+    /// The module must be passed to the [`stdb_reducers!`](crate::stdb_reducers) macro,
+    /// its callbacks are removed with `remove_reducer::<AnyReducerEvent<Reducer>>()`.
+    pub fn add_any_reducer_event(mut self) -> Self
+    where
+        C::Reducers: AnyReducerCallbacks,
+    {
+        self.channel_claims
+            .exclusive::<AnyReducerEvent<<C::Reducers as AnyReducerCallbacks>::Reducer>>();
+        let register_fn = |app: &mut App, conn: &'static C| {
+            let send = app.try_add_event_sender::<
+                AnyReducerEvent<<C::Reducers as AnyReducerCallbacks>::Reducer>,
+            >()?;
+            let register = move || conn.reducers().on_any_reducer(send.clone());
+
+            app.world_mut()
+                .get_resource_or_init::<StdbCallbackRegistry>()
+                .add_reducer::<AnyReducerEvent<<C::Reducers as AnyReducerCallbacks>::Reducer>>(
+                    Box::new(register),
+                );
+            Ok(())
+        };
+
+        // The register_fn will get called once the connection is built.
+        self.reducer_registers.push(Box::new(register_fn));
+
+        self
+    }
+}
+
+/// The span of a reducer callback, e.g. `stdb_reducer_callback{reducer="set_name"}`,
+/// entered by the callbacks generated by the macros.
+#[doc(hidden)]
+pub fn reducer_callback_span(reducer: &'static str) -> Span {
+    info_span!("stdb_reducer_callback", reducer)
+}
+
+/// Registers the callback of the reducer <E> in the [`StdbCallbackRegistry`], sending its events through `send`.
+pub(crate) fn register_reducer<C, M, E>(
+    app: &mut App,
+    conn: &'static C,
+    send: StdbSender<ReducerResultEvent<E>>,
+) where
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext + Sync,
+    M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
+    E: RegisterableReducerEvent<C, M> + Send + Sync + 'static,
+{
+    // Called now, and again each time the callback is restored after being removed.
+    let register = move || {
+        let id = E::set_stdb_callback(conn.reducers(), send.clone());
+        let remove: RemoveCallback = Box::new(move || E::remove_stdb_callback(conn.reducers(), id));
+        vec![remove]
+    };
+
+    app.world_mut()
+        .get_resource_or_init::<StdbCallbackRegistry>()
+        .add_reducer::<E>(Box::new(register));
+
+    let schedule = app.world().get_resource::<StdbChannelConfig>().map_or_else(
+        || PreUpdate.intern(),
+        |config| config.schedule_of::<ReducerResultEvent<E>>(),
+    );
+    add_reducer_diagnostics::<C, M, E>(app, schedule);
 }
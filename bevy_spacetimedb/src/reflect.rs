@@ -0,0 +1,67 @@
+use spacetimedb_sdk::{
+    __codegen::__sats::{i256, u256},
+    ConnectionId, Identity, ScheduleAt, TimeDuration, Timestamp,
+};
+
+/// The value of a field of a SpacetimeDB type when a row is built from its reflection,
+/// these types don't implement `Reflect` and their fields are ignored by `stdb_reflect!`.
+///
+/// The actual value of the field is lost, e.g. an `Identity` primary key of a row loaded from a scene is `Identity::ZERO`.
+pub trait StdbReflectDefault {
+    /// Returns the value of an ignored field.
+    fn reflect_default() -> Self;
+}
+
+impl StdbReflectDefault for Identity {
+    fn reflect_default() -> Self {
+        Identity::ZERO
+    }
+}
+
+impl StdbReflectDefault for ConnectionId {
+    fn reflect_default() -> Self {
+        ConnectionId::ZERO
+    }
+}
+
+impl StdbReflectDefault for Timestamp {
+    fn reflect_default() -> Self {
+        Timestamp::UNIX_EPOCH
+    }
+}
+
+impl StdbReflectDefault for TimeDuration {
+    fn reflect_default() -> Self {
+        TimeDuration::ZERO
+    }
+}
+
+impl StdbReflectDefault for ScheduleAt {
+    fn reflect_default() -> Self {
+        ScheduleAt::Time(Timestamp::UNIX_EPOCH)
+    }
+}
+
+impl StdbReflectDefault for u256 {
+    fn reflect_default() -> Self {
+        u256::ZERO
+    }
+}
+
+impl StdbReflectDefault for i256 {
+    fn reflect_default() -> Self {
+        i256::ZERO
+    }
+}
+
+impl<T> StdbReflectDefault for Option<T> {
+    fn reflect_default() -> Self {
+        None
+    }
+}
+
+impl<T> StdbReflectDefault for Vec<T> {
+    fn reflect_default() -> Self {
+        Vec::new()
+    }
+}
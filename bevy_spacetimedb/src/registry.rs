@@ -0,0 +1,349 @@
+use std::{any::TypeId, sync::Arc};
+
+use bevy::{
+    app::App,
+    platform::collections::HashMap,
+    prelude::{Commands, Resource, World},
+};
+use spacetimedb_sdk::{DbContext, Table, TableWithPrimaryKey};
+
+use crate::{
+    AddEventChannelAppExtensions, ReducerEventConnection, ReducerResultEvent,
+    RegisterableReducerEvent, StdbConnection, StdbPluginError, TableEvents,
+    channel_receiver::ChannelClaims,
+    reducers::register_reducer,
+    tables::{claim_table_channels, register_table},
+};
+
+/// Removes a callback registered on the connection.
+pub(crate) type RemoveCallback = Box<dyn FnOnce() + Send + Sync>;
+
+/// Registers callbacks on the connection, returning the functions removing them.
+pub(crate) type RegisterCallbacks = Box<dyn Fn() -> Vec<RemoveCallback> + Send + Sync>;
+
+/// The callbacks of one `add_table` or `add_reducer` call.
+struct Registration {
+    register: RegisterCallbacks,
+    // The functions removing the callbacks, `None` while they are removed.
+    removers: Option<Vec<RemoveCallback>>,
+}
+
+impl Registration {
+    fn new(register: RegisterCallbacks) -> Self {
+        Self {
+            removers: Some(register()),
+            register,
+        }
+    }
+
+    fn remove(&mut self) -> bool {
+        let Some(removers) = self.removers.take() else {
+            return false;
+        };
+        for remove in removers {
+            remove();
+        }
+        true
+    }
+
+    fn restore(&mut self) -> bool {
+        if self.removers.is_some() {
+            return false;
+        }
+        self.removers = Some((self.register)());
+        true
+    }
+}
+
+/// The callbacks registered on the connection for the tables and reducers, as a Bevy resource.
+///
+/// Tables are identified by their row type and reducers by their event type.
+/// Removing their callbacks stops their messages, e.g. when leaving a game mode,
+/// their event channels are kept so the callbacks can be restored later on.
+#[derive(Resource, Default)]
+pub struct StdbCallbackRegistry {
+    tables: HashMap<TypeId, Vec<Registration>>,
+    reducers: HashMap<TypeId, Vec<Registration>>,
+}
+
+impl StdbCallbackRegistry {
+    /// Registers the callbacks of a table with `register`, keeping the functions removing them.
+    pub(crate) fn add_table<TRow: 'static>(&mut self, register: RegisterCallbacks) {
+        self.tables
+            .entry(TypeId::of::<TRow>())
+            .or_default()
+            .push(Registration::new(register));
+    }
+
+    /// Registers the callbacks of a reducer with `register`, keeping the functions removing them.
+    pub(crate) fn add_reducer<E: 'static>(&mut self, register: RegisterCallbacks) {
+        self.reducers
+            .entry(TypeId::of::<E>())
+            .or_default()
+            .push(Registration::new(register));
+    }
+
+    /// Removes the callbacks of the tables of `TRow` rows, returns `false` if there were none.
+    pub fn remove_table<TRow: 'static>(&mut self) -> bool {
+        remove_all(self.tables.get_mut(&TypeId::of::<TRow>()))
+    }
+
+    /// Registers again the callbacks of the tables of `TRow` rows removed with [`Self::remove_table`],
+    /// returns `false` if there were none.
+    pub fn restore_table<TRow: 'static>(&mut self) -> bool {
+        restore_all(self.tables.get_mut(&TypeId::of::<TRow>()))
+    }
+
+    /// Returns `true` if callbacks are registered for a table of `TRow` rows.
+    pub fn is_table_registered<TRow: 'static>(&self) -> bool {
+        is_registered(self.tables.get(&TypeId::of::<TRow>()))
+    }
+
+    /// Removes the callbacks of the reducer of event `E`, returns `false` if there were none.
+    pub fn remove_reducer<E: 'static>(&mut self) -> bool {
+        remove_all(self.reducers.get_mut(&TypeId::of::<E>()))
+    }
+
+    /// Registers again the callbacks of the reducer of event `E` removed with [`Self::remove_reducer`],
+    /// returns `false` if there were none.
+    pub fn restore_reducer<E: 'static>(&mut self) -> bool {
+        restore_all(self.reducers.get_mut(&TypeId::of::<E>()))
+    }
+
+    /// Returns `true` if callbacks are registered for the reducer of event `E`.
+    pub fn is_reducer_registered<E: 'static>(&self) -> bool {
+        is_registered(self.reducers.get(&TypeId::of::<E>()))
+    }
+}
+
+fn remove_all(registrations: Option<&mut Vec<Registration>>) -> bool {
+    let mut removed = false;
+    for registration in registrations.into_iter().flatten() {
+        removed |= registration.remove();
+    }
+    removed
+}
+
+fn restore_all(registrations: Option<&mut Vec<Registration>>) -> bool {
+    let mut restored = false;
+    for registration in registrations.into_iter().flatten() {
+        restored |= registration.restore();
+    }
+    restored
+}
+
+fn is_registered(registrations: Option<&Vec<Registration>>) -> bool {
+    registrations
+        .into_iter()
+        .flatten()
+        .any(|registration| registration.removers.is_some())
+}
+
+/// Allows to remove and restore the callbacks of tables and reducers from systems,
+/// see [`StdbCallbackRegistry`].
+pub trait StdbCommandsExtensions {
+    /// Removes the callbacks of the tables of `TRow` rows.
+    fn remove_stdb_table<TRow: 'static>(&mut self);
+
+    /// Registers again the callbacks of the tables of `TRow` rows.
+    fn restore_stdb_table<TRow: 'static>(&mut self);
+
+    /// Removes the callbacks of the reducer of event `E`.
+    fn remove_stdb_reducer<E: 'static>(&mut self);
+
+    /// Registers again the callbacks of the reducer of event `E`.
+    fn restore_stdb_reducer<E: 'static>(&mut self);
+}
+
+impl StdbCommandsExtensions for Commands<'_, '_> {
+    fn remove_stdb_table<TRow: 'static>(&mut self) {
+        self.queue(|world: &mut World| {
+            world
+                .get_resource_or_init::<StdbCallbackRegistry>()
+                .remove_table::<TRow>();
+        });
+    }
+
+    fn restore_stdb_table<TRow: 'static>(&mut self) {
+        self.queue(|world: &mut World| {
+            world
+                .get_resource_or_init::<StdbCallbackRegistry>()
+                .restore_table::<TRow>();
+        });
+    }
+
+    fn remove_stdb_reducer<E: 'static>(&mut self) {
+        self.queue(|world: &mut World| {
+            world
+                .get_resource_or_init::<StdbCallbackRegistry>()
+                .remove_reducer::<E>();
+        });
+    }
+
+    fn restore_stdb_reducer<E: 'static>(&mut self) {
+        self.queue(|world: &mut World| {
+            world
+                .get_resource_or_init::<StdbCallbackRegistry>()
+                .restore_reducer::<E>();
+        });
+    }
+}
+
+/// Registers tables or reducers once the connection `C` is built.
+type PendingRegister<C> =
+    Box<dyn FnOnce(&mut App, &'static C) -> Result<(), StdbPluginError> + Send + Sync>;
+
+/// The registrations made with [`StdbAppExtensions`] before the [`crate::StdbPlugin`] of `C` is built.
+#[derive(Resource)]
+pub(crate) struct PendingRegistrations<C: 'static> {
+    pub(crate) registers: Vec<PendingRegister<C>>,
+    /// The event channels registered by `registers`.
+    pub(crate) claims: ChannelClaims,
+}
+
+impl<C: 'static> Default for PendingRegistrations<C> {
+    fn default() -> Self {
+        Self {
+            registers: Vec::new(),
+            claims: ChannelClaims::default(),
+        }
+    }
+}
+
+/// Allows to register tables and reducers from any plugin, whether the [`crate::StdbPlugin`]
+/// is added before or after it.
+pub trait StdbAppExtensions {
+    /// Registers a table with all events enabled, like [`crate::StdbPlugin::add_table`].
+    ///
+    /// Panics if a table of the same row type is already registered and the plugin is built.
+    ///
+    /// ```ignore
+    /// app.add_stdb_table::<DbConnection, _>(RemoteTables::planets);
+    /// ```
+    fn add_stdb_table<C, TTable>(
+        &mut self,
+        accessor: fn(&'static C::DbView) -> TTable,
+    ) -> &mut Self
+    where
+        C: DbContext + Sync + 'static,
+        TTable: Table<
+                Row: Send + Sync + Clone + 'static,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + TableWithPrimaryKey<UpdateCallbackId: Send + Sync + 'static>
+            + 'static;
+
+    /// Registers a table with the specified `events`, like [`crate::StdbPlugin::add_partial_table`].
+    fn add_stdb_partial_table<C, TTable>(
+        &mut self,
+        accessor: fn(&'static C::DbView) -> TTable,
+        events: TableEvents,
+    ) -> &mut Self
+    where
+        C: DbContext + Sync + 'static,
+        TTable: Table<
+                Row: Send + Sync + Clone + 'static,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + TableWithPrimaryKey<UpdateCallbackId: Send + Sync + 'static>
+            + 'static;
+
+    /// Registers a reducer event <E>, like [`crate::StdbPlugin::add_reducer`].
+    ///
+    /// Panics if <E> is already registered and the plugin is built.
+    fn add_stdb_reducer<E>(&mut self) -> &mut Self
+    where
+        E: ReducerEventConnection
+            + RegisterableReducerEvent<E::Connection, E::Module>
+            + Send
+            + Sync
+            + 'static;
+}
+
+impl StdbAppExtensions for App {
+    fn add_stdb_table<C, TTable>(&mut self, accessor: fn(&'static C::DbView) -> TTable) -> &mut Self
+    where
+        C: DbContext + Sync + 'static,
+        TTable: Table<
+                Row: Send + Sync + Clone + 'static,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + TableWithPrimaryKey<UpdateCallbackId: Send + Sync + 'static>
+            + 'static,
+    {
+        self.add_stdb_partial_table::<C, TTable>(accessor, TableEvents::all())
+    }
+
+    fn add_stdb_partial_table<C, TTable>(
+        &mut self,
+        accessor: fn(&'static C::DbView) -> TTable,
+        events: TableEvents,
+    ) -> &mut Self
+    where
+        C: DbContext + Sync + 'static,
+        TTable: Table<
+                Row: Send + Sync + Clone + 'static,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + TableWithPrimaryKey<UpdateCallbackId: Send + Sync + 'static>
+            + 'static,
+    {
+        let mut claims = ChannelClaims::default();
+        claim_table_channels::<TTable::Row>(&mut claims, events, false);
+        on_stdb_connection::<C>(
+            self,
+            claims,
+            Box::new(move |app, conn| register_table(app, conn, Arc::new(accessor), events, false)),
+        )
+    }
+
+    fn add_stdb_reducer<E>(&mut self) -> &mut Self
+    where
+        E: ReducerEventConnection
+            + RegisterableReducerEvent<E::Connection, E::Module>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let mut claims = ChannelClaims::default();
+        claims.exclusive::<ReducerResultEvent<E>>();
+        on_stdb_connection::<E::Connection>(
+            self,
+            claims,
+            Box::new(|app, conn| {
+                let send = app.try_add_event_sender::<ReducerResultEvent<E>>()?;
+                register_reducer::<E::Connection, E::Module, E>(app, conn, send);
+                Ok(())
+            }),
+        )
+    }
+}
+
+/// Calls `register` now if the connection `C` is built, or once the [`crate::StdbPlugin`] builds it,
+/// which first checks the event channels `claims` it registers.
+fn on_stdb_connection<C: DbContext + Sync + 'static>(
+    app: &mut App,
+    claims: ChannelClaims,
+    register: PendingRegister<C>,
+) -> &mut App {
+    let conn = app
+        .world()
+        .get_resource::<StdbConnection<C>>()
+        .map(StdbConnection::static_conn);
+
+    match conn {
+        Some(conn) => {
+            if let Err(err) = register(app, conn) {
+                panic!("{err}");
+            }
+        }
+        None => {
+            let mut pending = app
+                .world_mut()
+                .get_resource_or_init::<PendingRegistrations<C>>();
+            pending.registers.push(register);
+            pending.claims.extend(&claims);
+        }
+    }
+    app
+}
@@ -0,0 +1,374 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use bevy::{
+    ecs::relationship::Relationship,
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use spacetimedb_sdk::__codegen as spacetime_codegen;
+
+use crate::{
+    RowChangeEvent, StdbPlugin,
+    mirror::{MirrorSet, MirroredRow, StdbMirror, mirror_schedule},
+};
+
+/// Returns the key of the parent of a child row.
+type ForeignKey<TChild, TParent> =
+    Arc<dyn Fn(&TChild) -> <TParent as MirroredRow>::Key + Send + Sync>;
+
+/// The links between the entities of the child rows `TChild` and of their parent rows `TParent`.
+#[derive(Resource)]
+struct StdbRelationship<TChild: MirroredRow, TParent: MirroredRow, R> {
+    foreign_key: ForeignKey<TChild, TParent>,
+    /// The entity and parent key of each child row.
+    links: HashMap<TChild::Key, (Entity, TParent::Key)>,
+    /// The entities of the child rows by parent key, whether the parent row was received or not.
+    children: HashMap<TParent::Key, HashSet<Entity>>,
+    relationship: PhantomData<R>,
+}
+
+impl<TChild: MirroredRow, TParent: MirroredRow, R> StdbRelationship<TChild, TParent, R> {
+    /// Links the `entity` of the child row `key` to the parent `parent_key`.
+    fn link(&mut self, key: TChild::Key, entity: Entity, parent_key: TParent::Key) {
+        if let Some((_, previous)) = self.links.get(&key)
+            && *previous != parent_key
+            && let Some(children) = self.children.get_mut(previous)
+        {
+            children.remove(&entity);
+        }
+        self.children
+            .entry(parent_key.clone())
+            .or_default()
+            .insert(entity);
+        self.links.insert(key, (entity, parent_key));
+    }
+
+    /// Removes the link of the child row `key`, deleted or whose key changed.
+    fn unlink(&mut self, key: &TChild::Key) {
+        if let Some((entity, parent_key)) = self.links.remove(key)
+            && let Some(children) = self.children.get_mut(&parent_key)
+        {
+            children.remove(&entity);
+            if children.is_empty() {
+                self.children.remove(&parent_key);
+            }
+        }
+    }
+}
+
+impl<
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext + Sync,
+    M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
+> StdbPlugin<C, M>
+{
+    /// Makes the entities of the rows `TChild` children of the entities of their parent row `TParent`
+    /// with [`ChildOf`], the parent being found by the key returned by `foreign_key`, e.g. `|planet| planet.system_id`.
+    ///
+    /// Both tables must be mirrored, e.g. with [`Self::add_mirrored_table`]. A child row received before its parent
+    /// is attached once the parent row is received, and is detached when the parent row is deleted,
+    /// so it is not despawned along with it. The tables may be drained in different schedules,
+    /// each side of the relationship is updated in the schedule of its table.
+    pub fn add_relationship<TChild, TParent>(
+        self,
+        foreign_key: impl Fn(&TChild) -> TParent::Key + Send + Sync + 'static,
+    ) -> Self
+    where
+        TChild: MirroredRow,
+        TParent: MirroredRow,
+    {
+        self.add_relationship_with::<TChild, TParent, ChildOf>(foreign_key)
+    }
+
+    /// Same as [`Self::add_relationship`] with the relationship component `R` instead of [`ChildOf`].
+    pub fn add_relationship_with<TChild, TParent, R>(
+        mut self,
+        foreign_key: impl Fn(&TChild) -> TParent::Key + Send + Sync + 'static,
+    ) -> Self
+    where
+        TChild: MirroredRow,
+        TParent: MirroredRow,
+        R: Relationship,
+    {
+        let foreign_key: ForeignKey<TChild, TParent> = Arc::new(foreign_key);
+        let register = move |app: &mut App, _: &'static C| {
+            register_relationship::<TChild, TParent, R>(app, foreign_key.clone());
+            Ok(())
+        };
+        self.table_registers.push(Box::new(register));
+
+        self
+    }
+}
+
+/// Adds the [`StdbRelationship`] and its systems, in the schedules draining the child and the parent rows.
+fn register_relationship<TChild, TParent, R>(
+    app: &mut App,
+    foreign_key: ForeignKey<TChild, TParent>,
+) where
+    TChild: MirroredRow,
+    TParent: MirroredRow,
+    R: Relationship,
+{
+    let child_schedule = mirror_schedule::<TChild>(app);
+    let parent_schedule = mirror_schedule::<TParent>(app);
+    app.insert_resource(StdbRelationship::<TChild, TParent, R> {
+        foreign_key,
+        links: HashMap::default(),
+        children: HashMap::default(),
+        relationship: PhantomData,
+    })
+    .add_systems(
+        child_schedule,
+        attach_children::<TChild, TParent, R>.in_set(MirrorSet::Attach),
+    )
+    .add_systems(
+        parent_schedule,
+        (
+            detach_children::<TChild, TParent, R>.in_set(MirrorSet::Detach),
+            attach_waiting_children::<TChild, TParent, R>.in_set(MirrorSet::Attach),
+        ),
+    );
+}
+
+/// Detaches the children of the deleted parent rows before their entity is despawned,
+/// and of the parent rows whose key changed.
+fn detach_children<TChild, TParent, R>(
+    mut parents: MessageReader<RowChangeEvent<TParent>>,
+    relationship: Res<StdbRelationship<TChild, TParent, R>>,
+    mut commands: Commands,
+) where
+    TChild: MirroredRow,
+    TParent: MirroredRow,
+    R: Relationship,
+{
+    for change in parents.read() {
+        let key = match change {
+            RowChangeEvent::Delete(parent) => parent.key(),
+            RowChangeEvent::Update { old, new } if old.key() != new.key() => old.key(),
+            _ => continue,
+        };
+        for child in relationship.children.get(&key).into_iter().flatten() {
+            commands.entity(*child).try_remove::<R>();
+        }
+    }
+}
+
+/// Attaches the changed child rows to their parent.
+fn attach_children<TChild, TParent, R>(
+    mut children: MessageReader<RowChangeEvent<TChild>>,
+    mut relationship: ResMut<StdbRelationship<TChild, TParent, R>>,
+    child_mirror: Res<StdbMirror<TChild>>,
+    parent_mirror: Res<StdbMirror<TParent>>,
+    mut commands: Commands,
+) where
+    TChild: MirroredRow,
+    TParent: MirroredRow,
+    R: Relationship,
+{
+    for change in children.read() {
+        let child = match change {
+            RowChangeEvent::Insert(child) => child,
+            // Detached from its previous parent first, the key of the child may have changed
+            RowChangeEvent::Update { old, new } => {
+                relationship.unlink(&old.key());
+                new
+            }
+            RowChangeEvent::Delete(child) => {
+                // Unless the entity still mirrors other rows with the same key
+                if child_mirror.entity(&child.key()).is_none() {
+                    relationship.unlink(&child.key());
+                }
+                continue;
+            }
+        };
+        let Some(entity) = child_mirror.entity(&child.key()) else {
+            continue;
+        };
+        let parent_key = (relationship.foreign_key)(child);
+        match parent_mirror.entity(&parent_key) {
+            Some(parent) => {
+                commands.entity(entity).try_insert(R::from(parent));
+            }
+            // Waits for the parent row
+            None => {
+                commands.entity(entity).try_remove::<R>();
+            }
+        }
+        relationship.link(child.key(), entity, parent_key);
+    }
+}
+
+/// Attaches the children waiting for the inserted parent rows, or for the new key of the updated ones.
+fn attach_waiting_children<TChild, TParent, R>(
+    mut parents: MessageReader<RowChangeEvent<TParent>>,
+    relationship: Res<StdbRelationship<TChild, TParent, R>>,
+    parent_mirror: Res<StdbMirror<TParent>>,
+    mut commands: Commands,
+) where
+    TChild: MirroredRow,
+    TParent: MirroredRow,
+    R: Relationship,
+{
+    for change in parents.read() {
+        let (RowChangeEvent::Insert(parent) | RowChangeEvent::Update { new: parent, .. }) = change
+        else {
+            continue;
+        };
+        let key = parent.key();
+        let Some(parent) = parent_mirror.entity(&key) else {
+            continue;
+        };
+        for child in relationship.children.get(&key).into_iter().flatten() {
+            commands.entity(*child).try_insert(R::from(parent));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use bevy::ecs::schedule::ScheduleLabel;
+
+    use super::*;
+    use crate::{
+        StdbChannelConfig,
+        mirror::{RowComponentMapper, register_mirror},
+    };
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct StarSystem {
+        id: u32,
+    }
+
+    impl MirroredRow for StarSystem {
+        type Key = u32;
+
+        fn key(&self) -> u32 {
+            self.id
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Planet {
+        id: u32,
+        system_id: u32,
+    }
+
+    impl MirroredRow for Planet {
+        type Key = u32;
+
+        fn key(&self) -> u32 {
+            self.id
+        }
+    }
+
+    /// The planets drained in `PostUpdate`, after the star systems drained in `PreUpdate`.
+    fn relationship_app() -> App {
+        let mut config = StdbChannelConfig::default();
+        config
+            .message_schedules
+            .insert(TypeId::of::<RowChangeEvent<Planet>>(), PostUpdate.intern());
+        let mut app = App::new();
+        app.insert_resource(config)
+            .add_message::<RowChangeEvent<StarSystem>>()
+            .add_message::<RowChangeEvent<Planet>>();
+        register_mirror::<RowComponentMapper<StarSystem>>(&mut app);
+        register_mirror::<RowComponentMapper<Planet>>(&mut app);
+        register_relationship::<Planet, StarSystem, ChildOf>(
+            &mut app,
+            Arc::new(|planet: &Planet| planet.system_id),
+        );
+        app
+    }
+
+    fn apply<TRow: MirroredRow>(app: &mut App, change: RowChangeEvent<TRow>) {
+        app.world_mut().write_message(change);
+        app.update();
+    }
+
+    fn entity<TRow: MirroredRow>(app: &App, key: TRow::Key) -> Option<Entity> {
+        app.world().resource::<StdbMirror<TRow>>().entity(&key)
+    }
+
+    fn parent_of(app: &App, entity: Entity) -> Option<Entity> {
+        app.world().get::<ChildOf>(entity).map(ChildOf::parent)
+    }
+
+    #[test]
+    fn updated_children_are_moved_to_their_new_parent() {
+        let mut app = relationship_app();
+        apply(&mut app, RowChangeEvent::Insert(StarSystem { id: 1 }));
+        apply(&mut app, RowChangeEvent::Insert(StarSystem { id: 2 }));
+        let planet = Planet {
+            id: 10,
+            system_id: 1,
+        };
+        apply(&mut app, RowChangeEvent::Insert(planet.clone()));
+        let child = entity::<Planet>(&app, 10).unwrap();
+        assert_eq!(parent_of(&app, child), entity::<StarSystem>(&app, 1));
+
+        // With a new key too, so the link of the old key must not be left behind
+        apply(
+            &mut app,
+            RowChangeEvent::Update {
+                old: planet,
+                new: Planet {
+                    id: 11,
+                    system_id: 2,
+                },
+            },
+        );
+        assert_eq!(entity::<Planet>(&app, 11), Some(child));
+        assert_eq!(parent_of(&app, child), entity::<StarSystem>(&app, 2));
+
+        apply(&mut app, RowChangeEvent::Delete(StarSystem { id: 1 }));
+        assert!(app.world().get_entity(child).is_ok());
+        assert_eq!(parent_of(&app, child), entity::<StarSystem>(&app, 2));
+    }
+
+    #[test]
+    fn children_outlive_their_deleted_parent_and_wait_for_it() {
+        let mut app = relationship_app();
+        let system = StarSystem { id: 1 };
+        let planet = Planet {
+            id: 10,
+            system_id: 1,
+        };
+        apply(&mut app, RowChangeEvent::Insert(system.clone()));
+        apply(&mut app, RowChangeEvent::Insert(planet.clone()));
+        let child = entity::<Planet>(&app, 10).unwrap();
+
+        // Detached in the schedule of the star systems, before the planets are drained
+        apply(&mut app, RowChangeEvent::Delete(system.clone()));
+        assert!(app.world().get_entity(child).is_ok());
+        assert_eq!(parent_of(&app, child), None);
+
+        apply(&mut app, RowChangeEvent::Insert(system));
+        assert_eq!(parent_of(&app, child), entity::<StarSystem>(&app, 1));
+
+        apply(&mut app, RowChangeEvent::Delete(planet));
+        assert!(app.world().get_entity(child).is_err());
+        let relationship = app
+            .world()
+            .resource::<StdbRelationship<Planet, StarSystem, ChildOf>>();
+        assert!(relationship.links.is_empty() && relationship.children.is_empty());
+    }
+
+    #[test]
+    fn children_received_before_their_parent_are_attached_with_it() {
+        let mut app = relationship_app();
+        apply(
+            &mut app,
+            RowChangeEvent::Insert(Planet {
+                id: 10,
+                system_id: 1,
+            }),
+        );
+        let child = entity::<Planet>(&app, 10).unwrap();
+        assert_eq!(parent_of(&app, child), None);
+
+        apply(&mut app, RowChangeEvent::Insert(StarSystem { id: 1 }));
+        assert_eq!(parent_of(&app, child), entity::<StarSystem>(&app, 1));
+    }
+}
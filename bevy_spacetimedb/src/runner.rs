@@ -0,0 +1,146 @@
+use std::{future::Future, pin::Pin, sync::Arc, thread::JoinHandle};
+
+use bevy::{
+    app::{App, AppExit, Last},
+    ecs::schedule::{InternedScheduleLabel, IntoScheduleConfigs, SystemSet},
+    log::error,
+    prelude::{MessageReader, Res, ResMut, Resource},
+    tasks::{IoTaskPool, Task, block_on, poll_once},
+};
+use spacetimedb_sdk::DbContext;
+
+use crate::{StdbConnection, StdbDisconnectedEvent};
+
+/// A function returning the future which advances the connection, e.g. `DbConnection::run_async`.
+pub(crate) type AsyncRunFn<C> = Arc<
+    dyn Fn(&'static C) -> Pin<Box<dyn Future<Output = spacetimedb_sdk::Result<()>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// How the connection processes the messages it receives.
+pub(crate) enum RunMode<C: 'static> {
+    /// A function spawning a thread which advances the connection, e.g. `DbConnection::run_threaded`.
+    Threaded(fn(&C) -> JoinHandle<()>),
+    /// A function advancing the connection, called every frame before the event channels are drained,
+    /// e.g. `DbConnection::frame_tick`.
+    FrameTick(fn(&C) -> spacetimedb_sdk::Result<()>),
+    /// A future advancing the connection, spawned on the [`IoTaskPool`], e.g. `DbConnection::run_async`.
+    Async(AsyncRunFn<C>),
+}
+
+impl<C: 'static> Clone for RunMode<C> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Threaded(run_fn) => Self::Threaded(*run_fn),
+            Self::FrameTick(frame_tick) => Self::FrameTick(*frame_tick),
+            Self::Async(run_fn) => Self::Async(run_fn.clone()),
+        }
+    }
+}
+
+/// The system set in which the connection is advanced when running with [`RunMode::FrameTick`],
+/// ordered before [`crate::StdbSet::Drain`] so messages are delivered during the same frame.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct FrameTickSet;
+
+impl<C: DbContext + Send + Sync + 'static> RunMode<C> {
+    /// Starts advancing the `conn`ection, the frame tick runs in the `schedule` draining the event channels.
+    pub(crate) fn run(self, app: &mut App, conn: &'static C, schedule: InternedScheduleLabel) {
+        match self {
+            RunMode::Threaded(run_fn) => {
+                run_fn(conn);
+            }
+            RunMode::FrameTick(frame_tick) => {
+                app.add_systems(
+                    schedule,
+                    (move |stdb: Res<StdbConnection<C>>| match frame_tick(stdb.conn()) {
+                        // Reported through the StdbDisconnectedEvent
+                        Ok(()) | Err(spacetimedb_sdk::Error::Disconnected) => {}
+                        Err(err) => error!("Failed to advance the SpacetimeDB connection: {err}"),
+                    })
+                    .run_if(|stdb: Res<StdbConnection<C>>| stdb.is_active())
+                    .in_set(FrameTickSet),
+                );
+            }
+            RunMode::Async(run_fn) => {
+                let task = IoTaskPool::get().spawn(run_fn(conn));
+                app.insert_resource(StdbConnectionTask(Some(task)))
+                    .add_systems(Last, stop_connection_task);
+            }
+        }
+    }
+}
+
+/// The task advancing the connection on the [`IoTaskPool`], when running with
+/// [`crate::StdbPlugin::with_async_run_fn`].
+///
+/// The task is cancelled when the connection is lost or the app exits.
+#[derive(Resource)]
+pub struct StdbConnectionTask(Option<Task<spacetimedb_sdk::Result<()>>>);
+
+impl StdbConnectionTask {
+    /// Returns `true` if the task is still advancing the connection.
+    pub fn is_running(&self) -> bool {
+        self.0.as_ref().is_some_and(|task| !task.is_finished())
+    }
+
+    /// Cancels the task, the connection won't process messages anymore.
+    pub fn cancel(&mut self) {
+        // Dropping a task cancels it.
+        self.0 = None;
+    }
+}
+
+fn stop_connection_task(
+    mut task: ResMut<StdbConnectionTask>,
+    mut exit: MessageReader<AppExit>,
+    mut disconnected: MessageReader<StdbDisconnectedEvent>,
+) {
+    if exit.read().count() > 0 || disconnected.read().count() > 0 {
+        task.cancel();
+        return;
+    }
+
+    if task.0.as_ref().is_some_and(Task::is_finished)
+        && let Some(Some(Err(err))) = task.0.take().map(|mut task| block_on(poll_once(&mut task)))
+    {
+        error!("The SpacetimeDB connection task failed: {err}");
+    }
+}
+
+/// The methods advancing a connection, which SpacetimeDB generates as inherent methods of `DbConnection`.
+///
+/// Implement it for your generated `DbConnection` with [`impl_runnable_connection!`](crate::impl_runnable_connection),
+/// so the [`crate::StdbPlugin`] can pick a run function on its own.
+pub trait RunnableConnection {
+    /// Spawn a thread which processes WebSocket messages as they are received.
+    fn run_threaded(&self) -> JoinHandle<()>;
+
+    /// Process all WebSocket messages waiting in the queue,
+    /// then return without `await`ing or blocking the current thread.
+    fn frame_tick(&self) -> spacetimedb_sdk::Result<()>;
+}
+
+/// Implements [`RunnableConnection`] for a generated `DbConnection`.
+///
+/// ```ignore
+/// mod stdb;
+/// use stdb::DbConnection;
+///
+/// bevy_spacetimedb::impl_runnable_connection!(DbConnection);
+/// ```
+#[macro_export]
+macro_rules! impl_runnable_connection {
+    ($conn:ty) => {
+        impl $crate::RunnableConnection for $conn {
+            fn run_threaded(&self) -> ::std::thread::JoinHandle<()> {
+                <$conn>::run_threaded(self)
+            }
+
+            fn frame_tick(&self) -> $crate::__private::spacetimedb_sdk::Result<()> {
+                <$conn>::frame_tick(self)
+            }
+        }
+    };
+}
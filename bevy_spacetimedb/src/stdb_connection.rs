@@ -1,18 +1,25 @@
 use bevy::prelude::Resource;
 use spacetimedb_sdk::{ConnectionId, DbContext, Identity, Result};
 
+use crate::diagnostics::ReducerCalls;
+
 #[derive(Resource)]
 /// A connection to the SpacetimeDB server, as a Bevy resource.
 /// This struct is a wrapper around a concrete-typed `DbContext` in your auto-generated.
 pub struct StdbConnection<T: DbContext + 'static> {
     /// The underlying connection.
     conn: &'static T,
+    /// The reducer calls waiting for their result, see [`Self::call_reducer`].
+    reducer_calls: ReducerCalls,
 }
 
 impl<T: DbContext> StdbConnection<T> {
     /// Create a new connection to the SpacetimeDB server.
     pub fn new(conn: &'static T) -> Self {
-        Self { conn }
+        Self {
+            conn,
+            reducer_calls: ReducerCalls::default(),
+        }
     }
 }
 
@@ -27,6 +34,33 @@ impl<T: DbContext> StdbConnection<T> {
         self.conn.reducers()
     }
 
+    /// Calls a reducer with `call`, recording the time of the call so the [`crate::StdbDiagnosticsPlugin`]
+    /// measures the latency of its result, whose reducer event is <E>:
+    ///
+    /// ```ignore
+    /// stdb.call_reducer::<SetName>(|reducers| reducers.set_name(name))?;
+    /// ```
+    ///
+    /// The results are matched to the calls in order, the calls of <E> made with [`Self::reducers`]
+    /// instead would skew the measurement.
+    pub fn call_reducer<E: 'static>(
+        &self,
+        call: impl FnOnce(&T::Reducers) -> Result<()>,
+    ) -> Result<()> {
+        // Recorded first, the result may be received before `call` returns
+        self.reducer_calls.record::<E>();
+        let result = call(self.conn.reducers());
+        if result.is_err() {
+            self.reducer_calls.cancel::<E>();
+        }
+        result
+    }
+
+    /// The reducer calls made with [`Self::call_reducer`] waiting for their result.
+    pub(crate) fn reducer_calls(&self) -> &ReducerCalls {
+        &self.reducer_calls
+    }
+
     /// Get a builder-pattern constructor for subscribing to queries,
     /// causing matching rows to be replicated into the client cache.
     pub fn subscription_builder(&self) -> T::SubscriptionBuilder {
@@ -63,8 +97,18 @@ impl<T: DbContext> StdbConnection<T> {
         self.conn.connection_id()
     }
 
+    /// Get the connection ID, `None` until the connection is established.
+    pub fn try_connection_id(&self) -> Option<ConnectionId> {
+        self.conn.try_connection_id()
+    }
+
     /// Access to the underlying connection, it's not recommended to use this method directly.
     pub fn conn(&self) -> &T {
         self.conn
     }
+
+    /// The underlying connection, which lives until the end of the program.
+    pub(crate) fn static_conn(&self) -> &'static T {
+        self.conn
+    }
 }
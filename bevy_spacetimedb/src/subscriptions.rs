@@ -0,0 +1,103 @@
+use bevy::{
+    app::PreUpdate, ecs::schedule::ScheduleLabel, platform::collections::HashSet, prelude::*,
+};
+
+use crate::{
+    StdbChannelConfig, StdbSender, StdbSet, SubscriptionSnapshotReady,
+    channel_receiver::barrier_sender,
+};
+
+/// Tracks the subscriptions whose snapshot is awaited, as a Bevy resource.
+///
+/// Name a subscription with [`Self::on_snapshot_ready`] to receive a [`SubscriptionSnapshotReady`]
+/// once all the messages of its initial rows were written:
+///
+/// ```ignore
+/// fn subscribe(stdb: SpacetimeDB, mut subscriptions: ResMut<StdbSubscriptions>) {
+///     stdb.subscription_builder()
+///         .on_applied(subscriptions.on_snapshot_ready("galaxy"))
+///         .subscribe(["SELECT * FROM star_systems", "SELECT * FROM planets"]);
+/// }
+/// ```
+#[derive(Resource)]
+pub struct StdbSubscriptions {
+    sender: StdbSender<SubscriptionSnapshotReady>,
+    loading: HashSet<String>,
+    ready: HashSet<String>,
+}
+
+impl StdbSubscriptions {
+    /// Marks the subscription `name` as loading, and returns its `on_applied` callback
+    /// sending the [`SubscriptionSnapshotReady`].
+    pub fn on_snapshot_ready<Ctx>(
+        &mut self,
+        name: impl Into<String>,
+    ) -> impl FnOnce(&Ctx) + Send + 'static {
+        let name = name.into();
+        self.ready.remove(&name);
+        self.loading.insert(name.clone());
+
+        let sender = self.sender.clone();
+        move |_ctx: &Ctx| sender.send(SubscriptionSnapshotReady { name })
+    }
+
+    /// Whether the snapshot of the subscription `name` was received.
+    pub fn is_ready(&self, name: &str) -> bool {
+        self.ready.contains(name)
+    }
+
+    /// Whether the snapshot of a subscription is awaited.
+    pub fn is_loading(&self) -> bool {
+        !self.loading.is_empty()
+    }
+
+    /// The names of the subscriptions whose snapshot is awaited.
+    pub fn loading(&self) -> impl Iterator<Item = &str> {
+        self.loading.iter().map(String::as_str)
+    }
+}
+
+/// A run condition which is `true` once the snapshot of the subscription `name` was received.
+pub fn stdb_snapshot_ready(
+    name: impl Into<String>,
+) -> impl FnMut(Option<Res<StdbSubscriptions>>) -> bool + Clone {
+    let name = name.into();
+    move |subscriptions: Option<Res<StdbSubscriptions>>| {
+        subscriptions.is_some_and(|subscriptions| subscriptions.is_ready(&name))
+    }
+}
+
+/// A run condition which is `true` once the snapshots of all the named subscriptions were received,
+/// and at least one was, e.g. to leave a loading state.
+pub fn stdb_snapshots_ready() -> impl FnMut(Option<Res<StdbSubscriptions>>) -> bool + Clone {
+    |subscriptions: Option<Res<StdbSubscriptions>>| {
+        subscriptions.is_some_and(|subscriptions| {
+            !subscriptions.is_loading() && !subscriptions.ready.is_empty()
+        })
+    }
+}
+
+/// Registers the [`StdbSubscriptions`] and the barrier channel of the [`SubscriptionSnapshotReady`].
+pub(crate) fn register_subscriptions(app: &mut App) {
+    let schedule = app.world().get_resource::<StdbChannelConfig>().map_or_else(
+        || PreUpdate.intern(),
+        |config| config.schedule_of::<SubscriptionSnapshotReady>(),
+    );
+    let sender = barrier_sender::<SubscriptionSnapshotReady>(app);
+    app.insert_resource(StdbSubscriptions {
+        sender,
+        loading: HashSet::default(),
+        ready: HashSet::default(),
+    })
+    .add_systems(schedule, track_snapshots.in_set(StdbSet::ApplyMirror));
+}
+
+fn track_snapshots(
+    mut snapshots: MessageReader<SubscriptionSnapshotReady>,
+    mut subscriptions: ResMut<StdbSubscriptions>,
+) {
+    for snapshot in snapshots.read() {
+        subscriptions.loading.remove(&snapshot.name);
+        subscriptions.ready.insert(snapshot.name.clone());
+    }
+}
@@ -1,15 +1,23 @@
-use std::{
-    any::TypeId,
-    sync::mpsc::{Sender, channel},
-};
+use std::sync::Arc;
 
-use bevy::app::App;
-use spacetimedb_sdk::{__codegen as spacetime_codegen, Table, TableWithPrimaryKey};
+use bevy::{
+    app::App,
+    log::{info_span, tracing::Span},
+    prelude::{Event, Message},
+    utils::prelude::ShortName,
+};
+use spacetimedb_sdk::{__codegen as spacetime_codegen, DbContext, Table, TableWithPrimaryKey};
 
-use crate::AddEventChannelAppExtensions;
+use crate::cache::StdbTableAccess;
+use crate::channel_receiver::ChannelClaims;
+use crate::registry::RemoveCallback;
+use crate::{
+    AddEventChannelAppExtensions, StdbCallbackRegistry, StdbChannelConfig, StdbPluginError,
+    StdbSender,
+};
 // Imports are marked as unused but they are useful for linking types in docs.
 // #[allow(unused_imports)]
-use crate::{DeleteEvent, InsertEvent, InsertUpdateEvent, StdbPlugin, UpdateEvent};
+use crate::{DeleteEvent, InsertEvent, InsertUpdateEvent, RowChangeEvent, StdbPlugin, UpdateEvent};
 
 /// Passed into [`StdbPlugin::add_table`] to determine which table events to register.
 #[derive(Debug, Default, Clone, Copy)]
@@ -38,6 +46,7 @@ impl TableEvents {
         }
     }
 
+    /// Register the insert and delete events, e.g. for a table without a primary key which has no update events.
     pub fn no_update() -> Self {
         Self {
             insert: true,
@@ -48,7 +57,7 @@ impl TableEvents {
 }
 
 impl<
-    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext,
+    C: spacetime_codegen::DbConnection<Module = M> + spacetimedb_sdk::DbContext + Sync,
     M: spacetime_codegen::SpacetimeModule<DbConnection = C>,
 > StdbPlugin<C, M>
 {
@@ -56,7 +65,12 @@ impl<
     pub fn add_table<TRow, TTable, F>(self, accessor: F) -> Self
     where
         TRow: Send + Sync + Clone + 'static,
-        TTable: Table<Row = TRow> + TableWithPrimaryKey<Row = TRow>,
+        TTable: Table<
+                Row = TRow,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + TableWithPrimaryKey<UpdateCallbackId: Send + Sync + 'static>
+            + 'static,
         F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
     {
         self.add_partial_table(accessor, TableEvents::all())
@@ -66,24 +80,19 @@ impl<
     pub fn add_partial_table<TRow, TTable, F>(mut self, accessor: F, events: TableEvents) -> Self
     where
         TRow: Send + Sync + Clone + 'static,
-        TTable: Table<Row = TRow> + TableWithPrimaryKey<Row = TRow>,
+        TTable: Table<
+                Row = TRow,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + TableWithPrimaryKey<UpdateCallbackId: Send + Sync + 'static>
+            + 'static,
         F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
     {
+        claim_table_channels::<TRow>(&mut self.channel_claims, events, false);
+        let accessor = Arc::new(accessor);
         // A closure that sets up events for the table
-        let register = move |plugin: &Self, app: &mut App, db: &'static C::DbView| {
-            let table = accessor(db);
-            if events.insert {
-                plugin.on_insert(app, &table);
-            }
-            if events.delete {
-                plugin.on_delete(app, &table);
-            }
-            if events.update {
-                plugin.on_update(app, &table);
-            }
-            if events.update && events.insert {
-                plugin.on_insert_update(app, &table);
-            }
+        let register = move |app: &mut App, conn: &'static C| {
+            register_table(app, conn, accessor.clone(), events, false)
         };
 
         // Store this table, and later when the plugin is built, call them on .
@@ -92,129 +101,371 @@ impl<
         self
     }
 
-    /// Register a Bevy event of type InsertEvent<TRow> for the `on_insert` event on the provided table.
-    fn on_insert<TRow>(&self, app: &mut App, table: &impl Table<Row = TRow>) -> &Self
+    /// Registers a table without a primary key, which has no update events, with its insert and delete events.
+    pub fn add_table_without_pk<TRow, TTable, F>(self, accessor: F) -> Self
     where
         TRow: Send + Sync + Clone + 'static,
+        TTable: Table<
+                Row = TRow,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + 'static,
+        F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
     {
-        let type_id = TypeId::of::<InsertEvent<TRow>>();
-
-        let mut map = self.event_senders.lock().unwrap();
-
-        let sender = map
-            .entry(type_id)
-            .or_insert_with(|| {
-                let (send, recv) = channel::<InsertEvent<TRow>>();
-                app.add_event_channel(recv);
-                Box::new(send)
-            })
-            .downcast_ref::<Sender<InsertEvent<TRow>>>()
-            .expect("Sender type mismatch")
-            .clone();
-
-        table.on_insert(move |_ctx, row| {
-            let event = InsertEvent { row: row.clone() };
-            let _ = sender.send(event);
-        });
-
-        self
+        self.add_partial_table_without_pk(accessor, TableEvents::no_update())
     }
 
-    /// Register a Bevy event of type DeleteEvent<TRow> for the `on_delete` event on the provided table.
-    fn on_delete<TRow>(&self, app: &mut App, table: &impl Table<Row = TRow>) -> &Self
+    /// Registers a table without a primary key with the specified `events`, [`TableEvents::update`] is ignored.
+    pub fn add_partial_table_without_pk<TRow, TTable, F>(
+        mut self,
+        accessor: F,
+        events: TableEvents,
+    ) -> Self
     where
         TRow: Send + Sync + Clone + 'static,
+        TTable: Table<
+                Row = TRow,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + 'static,
+        F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
     {
-        let type_id = TypeId::of::<DeleteEvent<TRow>>();
-
-        let mut map = self.event_senders.lock().unwrap();
-        let sender = map
-            .entry(type_id)
-            .or_insert_with(|| {
-                let (send, recv) = channel::<DeleteEvent<TRow>>();
-                app.add_event_channel(recv);
-                Box::new(send)
-            })
-            .downcast_ref::<Sender<DeleteEvent<TRow>>>()
-            .expect("Sender type mismatch")
-            .clone();
-
-        table.on_delete(move |_ctx, row| {
-            let event = DeleteEvent { row: row.clone() };
-            let _ = sender.send(event);
-        });
+        let events = TableEvents {
+            update: false,
+            ..events
+        };
+        claim_table_channels::<TRow>(&mut self.channel_claims, events, false);
+        let accessor = Arc::new(accessor);
+        let register = move |app: &mut App, conn: &'static C| {
+            register_table_without_pk(app, conn, accessor.clone(), events, false)
+        };
+        self.table_registers.push(Box::new(register));
 
         self
     }
+}
 
-    /// Register a Bevy event of type UpdateEvent<TRow> for the `on_update` event on the provided table.
-    fn on_update<TRow, TTable>(&self, app: &mut App, table: &TTable) -> &Self
-    where
-        TRow: Send + Sync + Clone + 'static,
-        TTable: Table<Row = TRow> + TableWithPrimaryKey<Row = TRow>,
+/// Registers the event channels of the `events` of a table, and its callbacks in the [`StdbCallbackRegistry`].
+///
+/// The [`RowChangeEvent`] is registered as well if `mirror` is set.
+/// Returns an error if a table of `TRow` rows is already registered.
+pub(crate) fn register_table<C, TRow, TTable, F>(
+    app: &mut App,
+    conn: &'static C,
+    accessor: Arc<F>,
+    events: TableEvents,
+    mirror: bool,
+) -> Result<(), StdbPluginError>
+where
+    C: DbContext + Sync,
+    TRow: Send + Sync + Clone + 'static,
+    TTable: Table<
+            Row = TRow,
+            InsertCallbackId: Send + Sync + 'static,
+            DeleteCallbackId: Send + Sync + 'static,
+        > + TableWithPrimaryKey<UpdateCallbackId: Send + Sync + 'static>
+        + 'static,
+    F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
+{
+    let senders = TableSenders::<TRow>::new(app, events, mirror)?;
+    let handle_accessor = accessor.clone();
+    app.insert_resource(StdbTableAccess::new(move || handle_accessor(conn.db())));
+
+    // Called now, and again each time the callbacks are restored after being removed.
+    let register = move || {
+        let table = accessor(conn.db());
+        let mut removers = Vec::new();
+        senders.on_insert_delete(conn, &accessor, &table, &mut removers);
+        senders.on_update(conn, &accessor, &table, &mut removers);
+        removers
+    };
+
+    app.world_mut()
+        .get_resource_or_init::<StdbCallbackRegistry>()
+        .add_table::<TRow>(Box::new(register));
+    Ok(())
+}
+
+/// Same as [`register_table`], for a table without a primary key whose `events` can't include updates.
+pub(crate) fn register_table_without_pk<C, TRow, TTable, F>(
+    app: &mut App,
+    conn: &'static C,
+    accessor: Arc<F>,
+    events: TableEvents,
+    mirror: bool,
+) -> Result<(), StdbPluginError>
+where
+    C: DbContext + Sync,
+    TRow: Send + Sync + Clone + 'static,
+    TTable: Table<
+            Row = TRow,
+            InsertCallbackId: Send + Sync + 'static,
+            DeleteCallbackId: Send + Sync + 'static,
+        > + 'static,
+    F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
+{
+    let events = TableEvents {
+        update: false,
+        ..events
+    };
+    let senders = TableSenders::<TRow>::new(app, events, mirror)?;
+    let handle_accessor = accessor.clone();
+    app.insert_resource(StdbTableAccess::new(move || handle_accessor(conn.db())));
+
+    let register = move || {
+        let table = accessor(conn.db());
+        let mut removers = Vec::new();
+        senders.on_insert_delete(conn, &accessor, &table, &mut removers);
+        removers
+    };
+
+    app.world_mut()
+        .get_resource_or_init::<StdbCallbackRegistry>()
+        .add_table::<TRow>(Box::new(register));
+    Ok(())
+}
+
+/// Claims the event channels registered by [`TableSenders::new`].
+pub(crate) fn claim_table_channels<TRow: Send + Sync + Clone + 'static>(
+    claims: &mut ChannelClaims,
+    events: TableEvents,
+    mirror: bool,
+) {
+    if events.insert {
+        claims.exclusive::<InsertEvent<TRow>>();
+    }
+    if events.delete {
+        claims.exclusive::<DeleteEvent<TRow>>();
+    }
+    if events.update {
+        claims.exclusive::<UpdateEvent<TRow>>();
+    }
+    if events.insert && events.update {
+        claims.exclusive::<InsertUpdateEvent<TRow>>();
+    }
+    if mirror {
+        claims.exclusive::<RowChangeEvent<TRow>>();
+    }
+}
+
+/// The senders of the events registered for a table.
+struct TableSenders<TRow> {
+    insert: Option<StdbSender<InsertEvent<TRow>>>,
+    delete: Option<StdbSender<DeleteEvent<TRow>>>,
+    update: Option<StdbSender<UpdateEvent<TRow>>>,
+    insert_update: Option<StdbSender<InsertUpdateEvent<TRow>>>,
+    row_change: Option<StdbSender<RowChangeEvent<TRow>>>,
+}
+
+impl<TRow: Send + Sync + Clone + 'static> TableSenders<TRow> {
+    /// Registers the event channels of `events`, and of the [`RowChangeEvent`] if `mirror` is set,
+    /// also triggered for observers if enabled in the [`StdbChannelConfig`].
+    ///
+    /// Returns an error if one of them is already registered, e.g. by registering the same table twice,
+    /// as the messages of both tables would be written to it.
+    fn new(app: &mut App, events: TableEvents, mirror: bool) -> Result<Self, StdbPluginError> {
+        let trigger = app
+            .world()
+            .get_resource::<StdbChannelConfig>()
+            .is_some_and(|config| config.trigger_events);
+
+        Ok(Self {
+            insert: events
+                .insert
+                .then(|| event_sender::<InsertEvent<TRow>>(app, trigger))
+                .transpose()?,
+            delete: events
+                .delete
+                .then(|| event_sender::<DeleteEvent<TRow>>(app, trigger))
+                .transpose()?,
+            update: events
+                .update
+                .then(|| event_sender::<UpdateEvent<TRow>>(app, trigger))
+                .transpose()?,
+            insert_update: (events.insert && events.update)
+                .then(|| event_sender::<InsertUpdateEvent<TRow>>(app, trigger))
+                .transpose()?,
+            row_change: mirror
+                .then(|| event_sender::<RowChangeEvent<TRow>>(app, trigger))
+                .transpose()?,
+        })
+    }
+
+    /// Registers the `on_insert` and `on_delete` callbacks of `table`, adding the functions removing them to `removers`.
+    fn on_insert_delete<C, TTable, F>(
+        &self,
+        conn: &'static C,
+        accessor: &Arc<F>,
+        table: &TTable,
+        removers: &mut Vec<RemoveCallback>,
+    ) where
+        C: DbContext + Sync,
+        TTable: Table<
+                Row = TRow,
+                InsertCallbackId: Send + Sync + 'static,
+                DeleteCallbackId: Send + Sync + 'static,
+            > + 'static,
+        F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
     {
-        let type_id = TypeId::of::<UpdateEvent<TRow>>();
-
-        let mut map = self.event_senders.lock().unwrap();
-        let sender = map
-            .entry(type_id)
-            .or_insert_with(|| {
-                let (send, recv) = channel::<UpdateEvent<TRow>>();
-                app.add_event_channel(recv);
-                Box::new(send)
-            })
-            .downcast_ref::<Sender<UpdateEvent<TRow>>>()
-            .expect("Sender type mismatch")
-            .clone();
-
-        table.on_update(move |_ctx, old, new| {
-            let event = UpdateEvent {
-                old: old.clone(),
-                new: new.clone(),
-            };
-            let _ = sender.send(event);
-        });
+        if let Some(sender) = self.insert.clone() {
+            let id = table.on_insert(move |_ctx, row| {
+                let _span = callback_span::<TRow>("insert").entered();
+                let event = InsertEvent { row: row.clone() };
+                sender.send(event);
+            });
+            removers.push(remover(conn, accessor, id, TTable::remove_on_insert));
+        }
 
-        self
+        if let Some(sender) = self.delete.clone() {
+            let id = table.on_delete(move |_ctx, row| {
+                let _span = callback_span::<TRow>("delete").entered();
+                let event = DeleteEvent { row: row.clone() };
+                sender.send(event);
+            });
+            removers.push(remover(conn, accessor, id, TTable::remove_on_delete));
+        }
+
+        if let Some(send) = self.insert_update.clone() {
+            let id = table.on_insert(move |_ctx, row| {
+                let _span = callback_span::<TRow>("insert_update").entered();
+                let event = InsertUpdateEvent {
+                    old: None,
+                    new: row.clone(),
+                };
+                send.send(event);
+            });
+            removers.push(remover(conn, accessor, id, TTable::remove_on_insert));
+        }
+
+        if let Some(sender) = self.row_change.clone() {
+            let id = table.on_insert(move |_ctx, row| {
+                let _span = callback_span::<TRow>("row_change").entered();
+                sender.send(RowChangeEvent::Insert(row.clone()));
+            });
+            removers.push(remover(conn, accessor, id, TTable::remove_on_insert));
+        }
+
+        if let Some(sender) = self.row_change.clone() {
+            let id = table.on_delete(move |_ctx, row| {
+                let _span = callback_span::<TRow>("row_change").entered();
+                sender.send(RowChangeEvent::Delete(row.clone()));
+            });
+            removers.push(remover(conn, accessor, id, TTable::remove_on_delete));
+        }
     }
 
-    /// Register a Bevy event of type InsertUpdateEvent<TRow> for the `on_insert` and `on_update` events on the provided table.
-    fn on_insert_update<TRow, TTable>(&self, app: &mut App, table: &TTable) -> &Self
-    where
-        TRow: Send + Sync + Clone + 'static,
-        TTable: Table<Row = TRow> + TableWithPrimaryKey<Row = TRow>,
+    /// Registers the `on_update` callbacks of `table`, adding the functions removing them to `removers`.
+    fn on_update<C, TTable, F>(
+        &self,
+        conn: &'static C,
+        accessor: &Arc<F>,
+        table: &TTable,
+        removers: &mut Vec<RemoveCallback>,
+    ) where
+        C: DbContext + Sync,
+        TTable: TableWithPrimaryKey<Row = TRow, UpdateCallbackId: Send + Sync + 'static> + 'static,
+        F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
     {
-        let type_id = TypeId::of::<InsertUpdateEvent<TRow>>();
-
-        let mut map = self.event_senders.lock().unwrap();
-        let send = map
-            .entry(type_id)
-            .or_insert_with(|| {
-                let (send, recv) = channel::<InsertUpdateEvent<TRow>>();
-                app.add_event_channel(recv);
-                Box::new(send)
-            })
-            .downcast_ref::<Sender<InsertUpdateEvent<TRow>>>()
-            .expect("Sender type mismatch")
-            .clone();
-
-        let send_update = send.clone();
-        table.on_update(move |_ctx, old, new| {
-            let event = InsertUpdateEvent {
-                old: Some(old.clone()),
-                new: new.clone(),
-            };
-            let _ = send_update.send(event);
-        });
-
-        table.on_insert(move |_ctx, row| {
-            let event = InsertUpdateEvent {
-                old: None,
-                new: row.clone(),
-            };
-            let _ = send.send(event);
-        });
+        if let Some(sender) = self.update.clone() {
+            let id = table.on_update(move |_ctx, old, new| {
+                let _span = callback_span::<TRow>("update").entered();
+                let event = UpdateEvent {
+                    old: old.clone(),
+                    new: new.clone(),
+                };
+                sender.send(event);
+            });
+            removers.push(remover(conn, accessor, id, TTable::remove_on_update));
+        }
 
-        self
+        if let Some(send) = self.insert_update.clone() {
+            let id = table.on_update(move |_ctx, old, new| {
+                let _span = callback_span::<TRow>("insert_update").entered();
+                let event = InsertUpdateEvent {
+                    old: Some(old.clone()),
+                    new: new.clone(),
+                };
+                send.send(event);
+            });
+            removers.push(remover(conn, accessor, id, TTable::remove_on_update));
+        }
+
+        if let Some(sender) = self.row_change.clone() {
+            let id = table.on_update(move |_ctx, old, new| {
+                let _span = callback_span::<TRow>("row_change").entered();
+                sender.send(RowChangeEvent::Update {
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            });
+            removers.push(remover(conn, accessor, id, TTable::remove_on_update));
+        }
+    }
+}
+
+/// The span of a table callback, e.g. `stdb_table_callback{table=Player source="insert"}`.
+fn callback_span<TRow>(source: &'static str) -> Span {
+    info_span!("stdb_table_callback", table = %ShortName::of::<TRow>(), source)
+}
+
+/// Registers the event channel for `T` and returns its sender, it is also triggered for observers if `trigger` is set.
+fn event_sender<T>(app: &mut App, trigger: bool) -> Result<StdbSender<T>, StdbPluginError>
+where
+    T: Message + Event + Clone,
+    for<'a> T::Trigger<'a>: Default,
+{
+    let sender = app.try_add_event_sender::<T>()?;
+    if trigger {
+        app.trigger_stdb_message::<T>();
+    }
+    Ok(sender)
+}
+
+/// Returns a function removing the callback `id` from the table with `remove`, e.g. `Table::remove_on_insert`.
+fn remover<C, TTable, F, Id>(
+    conn: &'static C,
+    accessor: &Arc<F>,
+    id: Id,
+    remove: fn(&TTable, Id),
+) -> RemoveCallback
+where
+    C: DbContext + Sync,
+    TTable: 'static,
+    F: 'static + Send + Sync + Fn(&'static C::DbView) -> TTable,
+    Id: Send + Sync + 'static,
+{
+    let accessor = accessor.clone();
+    Box::new(move || remove(&accessor(conn.db()), id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::type_name;
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct Player;
+
+    #[test]
+    fn registering_a_table_twice_fails() {
+        let mut app = App::new();
+        assert!(TableSenders::<Player>::new(&mut app, TableEvents::all(), false).is_ok());
+
+        let err = TableSenders::<Player>::new(&mut app, TableEvents::all(), true).err();
+        assert!(matches!(
+            err,
+            Some(StdbPluginError::DuplicateEventChannel(name)) if name == type_name::<InsertEvent<Player>>()
+        ));
+    }
+
+    #[test]
+    fn tables_of_different_rows_are_registered() {
+        #[derive(Clone)]
+        struct Planet;
+
+        let mut app = App::new();
+        assert!(TableSenders::<Player>::new(&mut app, TableEvents::all(), true).is_ok());
+        assert!(TableSenders::<Planet>::new(&mut app, TableEvents::no_update(), true).is_ok());
     }
 }
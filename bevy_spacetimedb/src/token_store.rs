@@ -0,0 +1,273 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{platform::collections::HashMap, prelude::Resource};
+
+/// The profile used when none is set with [`crate::StdbPlugin::with_profile`].
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A place where access tokens are persisted between runs, so the same `Identity` is reused.
+///
+/// Tokens are stored per profile, which allows several identities (e.g. one per local player)
+/// to live side by side in the same store.
+pub trait TokenStore: Send + Sync + 'static {
+    /// Loads the token saved for `profile`, if any.
+    fn load(&self, profile: &str) -> io::Result<Option<String>>;
+
+    /// Saves `token` for `profile`, replacing any previously saved token.
+    fn save(&self, profile: &str, token: &str) -> io::Result<()>;
+
+    /// Removes the token saved for `profile`. Does nothing if no token is saved.
+    fn forget(&self, profile: &str) -> io::Result<()>;
+}
+
+/// A [`TokenStore`] saving one file per profile in a directory.
+///
+/// Created with [`FileTokenStore::new`], tokens are stored under the user's config directory
+/// (e.g. `~/.config/<app_name>/stdb_tokens` on Linux).
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    dir: PathBuf,
+}
+
+impl FileTokenStore {
+    /// Stores tokens in the `stdb_tokens` directory of the user's config directory for `app_name`.
+    ///
+    /// Falls back to the current directory if the platform has no config directory.
+    pub fn new(app_name: impl AsRef<str>) -> Self {
+        let dir = dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(app_name.as_ref())
+            .join("stdb_tokens");
+        Self::in_dir(dir)
+    }
+
+    /// Stores tokens in `dir`, which is created on the first save.
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The path of the file holding the token of `profile`.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `profile` is empty or is not made of
+    /// ASCII letters, digits, `-` and `_`, so it cannot point outside of the store directory.
+    pub fn path(&self, profile: &str) -> io::Result<PathBuf> {
+        let is_valid = !profile.is_empty()
+            && profile
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !is_valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "invalid token profile {profile:?}, expected ASCII letters, digits, '-' and '_'"
+                ),
+            ));
+        }
+        Ok(self.dir.join(format!("{profile}.token")))
+    }
+}
+
+/// Creates or truncates the file at `path`, only readable and writable by its owner on unix.
+fn create_private_file(path: &std::path::Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        options.mode(0o600);
+        let file = options.open(path)?;
+        // The mode only applies to new files
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        Ok(file)
+    }
+    #[cfg(not(unix))]
+    options.open(path)
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self, profile: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.path(profile)?) {
+            Ok(token) => Ok(Some(token.trim().to_string())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&self, profile: &str, token: &str) -> io::Result<()> {
+        let path = self.path(profile)?;
+        fs::create_dir_all(&self.dir)?;
+
+        // Written next to the token then renamed, so a crash never leaves a truncated token
+        let tmp_path = path.with_extension("token.tmp");
+        let mut file = create_private_file(&tmp_path)?;
+        file.write_all(token.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp_path, path)
+    }
+
+    fn forget(&self, profile: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(profile)?) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A [`TokenStore`] keeping tokens in memory, they are lost when the application exits.
+///
+/// Useful for tests, or to share an identity between several connections of the same process.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<HashMap<String, String>>,
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self, profile: &str) -> io::Result<Option<String>> {
+        Ok(self.tokens.lock().unwrap().get(profile).cloned())
+    }
+
+    fn save(&self, profile: &str, token: &str) -> io::Result<()> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(profile.to_string(), token.to_string());
+        Ok(())
+    }
+
+    fn forget(&self, profile: &str) -> io::Result<()> {
+        self.tokens.lock().unwrap().remove(profile);
+        Ok(())
+    }
+}
+
+/// The [`TokenStore`] used by the plugin along with the active profile, as a Bevy resource.
+///
+/// Only inserted when a store is set with [`crate::StdbPlugin::with_token_store`].
+#[derive(Resource, Clone)]
+pub struct StdbTokenStore {
+    store: Arc<dyn TokenStore>,
+    profile: String,
+}
+
+impl StdbTokenStore {
+    pub(crate) fn new(store: Arc<dyn TokenStore>, profile: String) -> Self {
+        Self { store, profile }
+    }
+
+    /// The profile under which the token is saved.
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Access to the underlying store.
+    pub fn store(&self) -> &dyn TokenStore {
+        self.store.as_ref()
+    }
+
+    /// Loads the token saved for the active profile, if any.
+    pub fn load(&self) -> io::Result<Option<String>> {
+        self.store.load(&self.profile)
+    }
+
+    /// Saves `token` for the active profile.
+    pub fn save(&self, token: &str) -> io::Result<()> {
+        self.store.save(&self.profile, token)
+    }
+
+    /// Forgets the identity of the active profile.
+    ///
+    /// The current connection keeps its identity, a new anonymous `Identity`
+    /// will be generated by the host on the next connection.
+    pub fn forget_identity(&self) -> io::Result<()> {
+        self.store.forget(&self.profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(store: &dyn TokenStore) {
+        assert_eq!(store.load("alice").unwrap(), None);
+
+        store.save("alice", "token-1").unwrap();
+        store.save("bob", "token-2").unwrap();
+        assert_eq!(store.load("alice").unwrap().as_deref(), Some("token-1"));
+        assert_eq!(store.load("bob").unwrap().as_deref(), Some("token-2"));
+
+        store.save("alice", "token-3").unwrap();
+        assert_eq!(store.load("alice").unwrap().as_deref(), Some("token-3"));
+    }
+
+    fn forget(store: &dyn TokenStore) {
+        store.save("alice", "token-1").unwrap();
+        store.save("bob", "token-2").unwrap();
+
+        store.forget("alice").unwrap();
+        assert_eq!(store.load("alice").unwrap(), None);
+        assert_eq!(store.load("bob").unwrap().as_deref(), Some("token-2"));
+
+        // Forgetting a missing token is not an error
+        store.forget("alice").unwrap();
+    }
+
+    #[test]
+    fn memory_store_round_trip() {
+        round_trip(&MemoryTokenStore::default());
+    }
+
+    #[test]
+    fn memory_store_forget() {
+        forget(&MemoryTokenStore::default());
+    }
+
+    #[test]
+    fn file_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        round_trip(&FileTokenStore::in_dir(dir.path().join("tokens")));
+    }
+
+    #[test]
+    fn file_store_forget() {
+        let dir = tempfile::tempdir().unwrap();
+        forget(&FileTokenStore::in_dir(dir.path()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_store_token_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileTokenStore::in_dir(dir.path());
+        store.save("alice", "token-1").unwrap();
+
+        let mode = fs::metadata(store.path("alice").unwrap())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn file_store_rejects_invalid_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileTokenStore::in_dir(dir.path().join("tokens"));
+
+        for profile in ["", "../x", "a/b", "a\\b", ".", ".."] {
+            let err = store.save(profile, "token").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{profile:?}");
+            assert_eq!(
+                store.load(profile).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+        assert!(!dir.path().join("x.token").exists());
+    }
+}
@@ -1,21 +1,24 @@
 use bevy::{log::LogPlugin, prelude::*};
 use bevy_spacetimedb::{
-    ReadDeleteEvent, ReadInsertEvent, ReadInsertUpdateEvent, ReadReducerEvent,
-    ReadStdbConnectedEvent, ReadUpdateEvent, ReducerResultEvent, RegisterReducerEvent,
-    StdbConnection, StdbPlugin, TableEvents,
+    ReadAnyReducerEvent, ReadDeleteEvent, ReadInsertEvent, ReadInsertUpdateEvent, ReadReducerEvent,
+    ReadStdbConnectedEvent, ReadUpdateEvent, RegisterReducerEvent, StdbConnection, StdbPlugin,
 };
 use spacetimedb_sdk::ReducerEvent;
 use stdb::{DbConnection, Reducer};
 
-use crate::stdb::gs_register_reducer::gs_register;
-use crate::stdb::gs_set_ready_reducer::gs_set_ready;
-use crate::stdb::{
-    GameServersTableAccess, PlanetsTableAccess, Player, PlayersTableAccess, RemoteModule,
-    RemoteReducers, RemoteTables,
-};
+use crate::stdb::Player;
+
 mod stdb;
 
+// Generates `register_all_tables`, registering every table of the module.
+bevy_spacetimedb::stdb_tables!(stdb);
+// Allows `add_any_reducer_event`, observing every reducer of the module.
+bevy_spacetimedb::stdb_reducers!(stdb);
+// Implements `Reflect` for the rows and generates `register_all_types`.
+bevy_spacetimedb::stdb_reflect!(stdb);
+
 #[derive(Debug, RegisterReducerEvent)]
+#[stdb(module = crate::stdb)]
 #[allow(dead_code)]
 pub struct GsRegister {
     event: ReducerEvent<Reducer>,
@@ -23,29 +26,28 @@ pub struct GsRegister {
     port: u16,
 }
 
-#[derive(Debug, RegisterReducerEvent)]
-#[allow(dead_code)]
-pub struct GsSetReady {
-    event: ReducerEvent<Reducer>,
-}
+// Generates the `GsSetReady` event from the reducer's arguments.
+bevy_spacetimedb::stdb_reducer_event!(gs_set_ready);
 
 pub type SpacetimeDB<'a> = Res<'a, StdbConnection<DbConnection>>;
 
+// Lets `StdbPlugin::new()` run the connection without `with_run_fn`.
+bevy_spacetimedb::impl_runnable_connection!(DbConnection);
+
 pub fn main() {
     App::new()
         .add_plugins((MinimalPlugins, LogPlugin::default()))
+        .add_plugins(register_all_types)
         .add_plugins(
-            StdbPlugin::default()
-                .with_uri("http://localhost:3000")
-                .with_module_name("chat")
-                .with_run_fn(DbConnection::run_threaded)
-                .add_table(RemoteTables::planets)
-                .add_table(RemoteTables::players)
-                .add_table(RemoteTables::game_servers)
-                .add_partial_table(RemoteTables::players, TableEvents::no_update()) // Some tables
-                // do not have update events, especially those without primary keys.
-                .add_reducer::<GsRegister>()
-                .add_reducer::<GsSetReady>(),
+            register_all_tables(
+                StdbPlugin::new()
+                    .with_uri("http://localhost:3000")
+                    .with_module_name("chat"),
+            )
+            // Tables without a primary key are registered without update events.
+            .add_reducer::<GsRegister>()
+            .add_reducer::<GsSetReady>()
+            .add_any_reducer_event(),
         )
         .add_systems(Update, on_connected)
         .add_systems(Update, on_player_inserted)
@@ -54,6 +56,7 @@ pub fn main() {
         .add_systems(Update, on_player_inserted_updated)
         .add_systems(Update, on_gs_register)
         .add_systems(Update, on_gs_set_ready)
+        .add_systems(Update, on_any_reducer)
         .run();
 }
 
@@ -114,3 +117,9 @@ fn on_gs_set_ready(mut events: ReadReducerEvent<GsSetReady>) {
         info!("Game server set ready: {:?}", event.result);
     }
 }
+
+fn on_any_reducer(mut events: ReadAnyReducerEvent<Reducer>) {
+    for event in events.read() {
+        info!("Reducer {:?} called: {:?}", event.reducer(), event.status());
+    }
+}
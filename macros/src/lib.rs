@@ -1,7 +1,9 @@
-use heck::ToSnakeCase;
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{Data, DeriveInput, Fields, Ident, parse_macro_input};
+use syn::{DeriveInput, parse_macro_input};
+
+mod reducers;
+mod reflect;
+mod tables;
 
 /// This macro automatically generates the boilerplate code needed to register a reducer
 /// with the `StdbPlugin`.
@@ -11,74 +13,152 @@ use syn::{Data, DeriveInput, Fields, Ident, parse_macro_input};
 /// - The struct must have exactly one field named `event` of type `ReducerEvent<Reducer>`
 /// - All other fields must match the reducer's parameter types and order
 /// - Struct fields must be named (no tuple structs)
+/// - Without the `module` argument, `DbConnection`, `RemoteModule`, `RemoteReducers`,
+///   the reducer's extension trait (e.g. `set_name`) and callback id (e.g. `SetNameCallbackId`) must be in scope.
+///   The other items are referred to through `bevy_spacetimedb`, so no direct `spacetimedb_sdk` dependency is needed
+///
+/// ## Arguments
+///
+/// - `#[stdb(module = crate::stdb)]`: the module generated by SpacetimeDB, in which these items are looked up
 ///
 /// ## Example
 ///
 ///```no-run
 /// #[derive(RegisterReducerEvent)]
+/// #[stdb(module = crate::stdb)]
 /// pub struct SetName {
 ///     pub event: ReducerEvent<Reducer>,
 ///     pub name: String,
 /// }
 /// ```
-#[proc_macro_derive(RegisterReducerEvent)]
+#[proc_macro_derive(RegisterReducerEvent, attributes(stdb))]
 pub fn register_reducer_event_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let struct_name = &input.ident;
-    let struct_name_str = struct_name.to_string();
 
-    // Derive callback name directly from struct name (no suffix stripping)
-    let function_name = Ident::new(
-        &format!("on_{}", struct_name_str.to_snake_case()),
-        struct_name.span(),
-    );
+    reducers::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    // Extract named fields
-    let fields = match input.data {
-        Data::Struct(data_struct) => match data_struct.fields {
-            Fields::Named(fields_named) => fields_named.named,
-            _ => panic!("Struct must have named fields"),
-        },
-        _ => panic!("Only structs are supported"),
-    };
+/// This macro generates the event struct of a reducer from its arguments, deriving `RegisterReducerEvent`,
+/// so it follows the changes of the server schema.
+///
+/// It reads the `<reducer>_reducer.rs` file of the module generated by SpacetimeDB,
+/// the struct is named after the reducer and has an `event` field followed by the reducer's arguments.
+///
+/// ## Arguments
+///
+/// - `module = crate::bindings`: the module generated by SpacetimeDB, `crate::stdb` by default
+/// - `path = "src/bindings"`: the directory of the module relative to the crate root, `src/<module name>` by default
+///
+/// ## Example
+///
+///```no-run
+/// // pub struct GsRegister { pub event: ReducerEvent<Reducer>, pub ip: String, pub port: u16 }
+/// stdb_reducer_event!(gs_register);
+///
+/// StdbPlugin::new().add_reducer::<GsRegister>()
+/// ```
+#[proc_macro]
+pub fn stdb_reducer_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as reducers::ReducerEventInput);
+
+    reducers::expand_event_struct(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// This macro generates a function registering all the tables of a module generated by SpacetimeDB
+/// with the `StdbPlugin`, tables without a primary key are registered without their update events.
+///
+/// It reads the `*_table.rs` files of the module, in `src/<module name>` by default.
+///
+/// ## Arguments
+///
+/// - the module generated by SpacetimeDB, e.g. `stdb` or `crate::stdb`
+/// - `path = "src/stdb"`: the directory of the module, relative to the crate root
+/// - `name = register_galaxy_tables`: the name of the function, `register_all_tables` by default
+///
+/// ## Example
+///
+///```no-run
+/// mod stdb;
+///
+/// stdb_tables!(stdb);
+///
+/// register_all_tables(StdbPlugin::new().with_module_name("galaxy"))
+/// ```
+#[proc_macro]
+pub fn stdb_tables(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as tables::ModuleInput);
 
-    // Separate 'event' field from reducer parameters
-    let mut event_field = None;
-    let mut param_fields = Vec::new();
-    let mut param_idents = Vec::new();
+    tables::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    for field in fields {
-        let field_ident = field.ident.as_ref().expect("Field must have identifier");
-        if field_ident == "event" {
-            if event_field.is_some() {
-                panic!("Duplicate 'event' field");
-            }
-            event_field = Some(field);
-        } else {
-            param_idents.push(field_ident.clone());
-            param_fields.push(field);
-        }
-    }
+/// This macro implements `AnyReducerCallbacks` for the `RemoteReducers` of a module generated by SpacetimeDB,
+/// allowing `StdbPlugin::add_any_reducer_event` to observe every reducer of the module.
+///
+/// It reads the `*_reducer.rs` files of the module, in `src/<module name>` by default.
+///
+/// ## Arguments
+///
+/// - the module generated by SpacetimeDB, e.g. `stdb` or `crate::stdb`
+/// - `path = "src/stdb"`: the directory of the module, relative to the crate root
+///
+/// ## Example
+///
+///```no-run
+/// mod stdb;
+///
+/// stdb_reducers!(stdb);
+///
+/// StdbPlugin::new().add_any_reducer_event()
+/// ```
+#[proc_macro]
+pub fn stdb_reducers(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as reducers::StdbReducersInput);
 
-    if event_field.is_none() {
-        panic!("Struct must have an 'event' field");
-    }
+    reducers::expand_any_reducer(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    // Generate the implementation
-    let expanded = quote! {
-        impl bevy_spacetimedb::RegisterableReducerEvent<DbConnection, RemoteModule> for #struct_name {
-            fn set_stdb_callback(reducers: &RemoteReducers, sender: std::sync::mpsc::Sender<ReducerResultEvent<Self>>) {
-                reducers.#function_name(move |ctx, #(#param_idents),*| {
-                    sender
-                        .send(ReducerResultEvent::new(#struct_name {
-                            event: ctx.event.clone(),
-                            #(#param_idents: #param_idents.clone()),*
-                        }))
-                        .unwrap();
-                });
-            }
-        }
-    };
+/// This macro implements `Reflect` for the types of a module generated by SpacetimeDB,
+/// so rows can be shown and edited in inspectors and serialized in scenes,
+/// and generates a function registering them in the `App`, usable as a plugin.
+///
+/// It reads the `*_type.rs` files of the module, in `src/<module name>` by default.
+///
+/// ## Fields of SpacetimeDB types
+///
+/// SpacetimeDB types such as `Identity`, `ConnectionId` or `Timestamp` don't implement `Reflect`,
+/// and can't be reflected from outside of the SDK. The fields using them, including `Option` or `Vec` of them,
+/// are ignored: they are missing from inspectors, and a row built from its reflection, e.g. when loading a scene
+/// or deserializing a reflected row, gets a default value instead (`Identity::ZERO`, `Timestamp::UNIX_EPOCH`...),
+/// even for its primary key. The ignored fields are listed in the documentation of the generated function.
+///
+/// ## Arguments
+///
+/// - the module generated by SpacetimeDB, e.g. `stdb` or `crate::stdb`
+/// - `path = "src/stdb"`: the directory of the module, relative to the crate root
+/// - `name = register_galaxy_types`: the name of the function, `register_all_types` by default
+///
+/// ## Example
+///
+///```no-run
+/// mod stdb;
+///
+/// stdb_reflect!(stdb);
+///
+/// App::new().add_plugins(register_all_types);
+/// ```
+#[proc_macro]
+pub fn stdb_reflect(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as tables::ModuleInput);
 
-    TokenStream::from(expanded)
+    reflect::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
@@ -0,0 +1,161 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, File, Item, Type, parse_quote};
+
+use crate::{
+    reducers::{imported_types, qualify_type},
+    tables::{ModuleInput, module_file, read_generated_files},
+};
+
+/// The namespaces of the generated module's SpacetimeDB types, which don't implement `Reflect`.
+const SDK_NAMESPACES: [&str; 3] = ["__sdk", "__sats", "__lib"];
+
+/// Reads the type declared by a `<type>_type.rs` file generated by SpacetimeDB,
+/// e.g. `pub struct Player { pub id: u64, pub name: String }`.
+fn parse_type_file(file: &File) -> Option<DeriveInput> {
+    file.items.iter().find_map(|item| match item {
+        Item::Struct(item_struct) => Some(item_struct.clone().into()),
+        Item::Enum(item_enum) => Some(item_enum.clone().into()),
+        _ => None,
+    })
+}
+
+/// Returns `true` if `ty` uses a SpacetimeDB type, e.g. `__sdk::Identity` or `Vec<__sdk::Timestamp>`.
+fn uses_sdk_type(ty: &TokenStream) -> bool {
+    ty.clone().into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => SDK_NAMESPACES.iter().any(|ns| ident == ns),
+        proc_macro2::TokenTree::Group(group) => uses_sdk_type(&group.stream()),
+        _ => false,
+    })
+}
+
+/// The path of the generated module for `TypePath`, e.g. `example_app::stdb` for `crate::stdb`.
+fn type_path(input: &ModuleInput) -> String {
+    let crate_name = std::env::var("CARGO_CRATE_NAME").unwrap_or_else(|_| "crate".to_string());
+    let mut segments: Vec<String> = input
+        .module
+        .segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect();
+    if segments.first().is_some_and(|segment| segment == "crate") {
+        segments.remove(0);
+    }
+    segments.insert(0, crate_name);
+    segments.join("::")
+}
+
+pub(crate) fn expand(input: ModuleInput) -> syn::Result<TokenStream> {
+    let (dir, error_span) = input.dir()?;
+    let module_name = input.module_name();
+    let type_path = type_path(&input);
+    let module = &input.module;
+
+    let mut reflected = Vec::new();
+    let mut type_names = Vec::new();
+    let mut tracked_files = Vec::from_iter(module_file(&dir));
+    let mut ignored_fields = Vec::new();
+    for (file, parsed) in read_generated_files(&dir, "_type.rs", error_span)? {
+        let mut item = parse_type_file(&parsed).ok_or_else(|| {
+            syn::Error::new(
+                error_span,
+                format!("{} is not a type generated by SpacetimeDB", file.display()),
+            )
+        })?;
+
+        // Fields of SpacetimeDB types are ignored, their value is kept when cloning the row
+        // but replaced by `StdbReflectDefault` when the row is built from its reflection
+        let imported = imported_types(&parsed);
+        let fields: Vec<&mut Fields> = match &mut item.data {
+            Data::Struct(data_struct) => vec![&mut data_struct.fields],
+            Data::Enum(data_enum) => data_enum
+                .variants
+                .iter_mut()
+                .map(|variant| &mut variant.fields)
+                .collect(),
+            Data::Union(_) => Vec::new(),
+        };
+        for field in fields.into_iter().flat_map(|fields| fields.iter_mut()) {
+            let ty = &field.ty;
+            let ty = quote!(#ty);
+            if uses_sdk_type(&ty) {
+                if let Some(field_ident) = &field.ident {
+                    ignored_fields.push(format!("`{}::{field_ident}`", item.ident));
+                }
+                field.attrs.push(parse_quote!(
+                    #[reflect(ignore, default = "bevy_spacetimedb::StdbReflectDefault::reflect_default")]
+                ));
+            }
+            field.ty = syn::parse2::<Type>(qualify_type(ty, module, &imported))?;
+        }
+        item.attrs = vec![
+            parse_quote!(#[reflect(Debug, PartialEq, Clone)]),
+            parse_quote!(#[type_path = #type_path]),
+        ];
+
+        let ident = &item.ident;
+        reflected.push(quote! {
+            const _: () = {
+                use #module::#ident;
+
+                bevy_spacetimedb::__private::bevy::reflect::impl_reflect! {
+                    #item
+                }
+            };
+        });
+        type_names.push(item.ident.clone());
+        tracked_files.push(file.display().to_string());
+    }
+
+    let function_name = input
+        .name
+        .clone()
+        .unwrap_or_else(|| format_ident!("register_all_types"));
+    let mut doc = format!("Registers the types of the `{module_name}` module for reflection.");
+    if !ignored_fields.is_empty() {
+        doc.push_str(&format!(
+            "\n\nThese fields of SpacetimeDB types are not reflected, they are set to a default value \
+             when a row is built from its reflection, e.g. when loading a scene: {}.",
+            ignored_fields.join(", ")
+        ));
+    }
+
+    Ok(quote! {
+        #(#reflected)*
+
+        #[doc = #doc]
+        pub fn #function_name(app: &mut bevy_spacetimedb::__private::bevy::app::App) {
+            // Recompiles when the module is generated again
+            #(const _: &[u8] = include_bytes!(#tracked_files);)*
+
+            app #(.register_type::<#module::#type_names>())*;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::tests::fixture;
+
+    #[test]
+    fn type_is_parsed() {
+        let item = parse_type_file(&fixture("player_type.rs")).unwrap();
+        assert_eq!(item.ident, "Player");
+        assert!(
+            parse_type_file(&syn::parse_quote!(
+                use super::point_type::Point;
+            ))
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn sdk_fields_are_ignored() {
+        let input: ModuleInput = syn::parse_quote!(crate::stdb, path = "tests/fixtures/stdb");
+        let expanded = expand(input).unwrap().to_string();
+        assert!(expanded.contains("`Player::id`, `Player::current_server`"));
+        assert!(expanded.contains(&quote!(.register_type::<crate::stdb::Player>()).to_string()));
+        assert!(expanded.contains(&quote!(.register_type::<crate::stdb::Point>()).to_string()));
+    }
+}